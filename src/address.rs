@@ -1,5 +1,6 @@
+use crate::address_formatter::AddressFormatter;
+use crate::geo::Point;
 use dawa_autocomplete::SizeOf;
-use std::fmt::Display;
 use uuid::Uuid;
 
 #[derive(Debug, Eq, PartialEq, PartialOrd, Ord, Clone, SizeOf)]
@@ -14,6 +15,10 @@ pub struct Address {
     pub zip: String,
     pub placename: String,
     pub city: String,
+    pub coordinates: Option<Point>,
+    pub access_address_id: Uuid,
+    pub large_recipient_zip: String,
+    pub large_recipient_city: String,
 }
 
 // impl Display for Address {
@@ -29,29 +34,18 @@ impl From<Address> for String {
 }
 
 impl Address {
+    /// DAWA's `betegnelse`, e.g. `Maribovej 15, st. tv, 2500 Valby`.
     pub fn display_name(&self) -> String {
-        let mut display_name = format!("{} {}", self.street, self.number);
-        if !self.floor.is_empty() {
-            display_name.push_str(&format!(", {}.", self.floor));
-        }
-        if self.floor.is_empty() && !self.door.is_empty() {
-            display_name.push_str(&format!(","));
-        }
-        if !self.door.is_empty() {
-            display_name.push_str(&format!(" {}", self.door));
-        }
-        if !self.placename.is_empty() {
-            display_name.push_str(&format!(", {}", self.placename));
-        }
-        display_name.push_str(&format!(", {} {}", self.zip, self.city));
-        display_name
+        AddressFormatter::default().format(self)
     }
 
+    /// The `betegnelse` of the adgangsadresse, without floor and door.
     pub fn access_address_name(&self) -> String {
-        format!(
-            "{} {}, {},{}",
-            self.street, self.number, self.zip, self.city
-        )
+        AddressFormatter {
+            access_address: true,
+            ..AddressFormatter::default()
+        }
+        .format(self)
     }
 }
 
@@ -68,6 +62,10 @@ impl Default for Address {
             zip: "".to_string(),
             placename: "".to_string(),
             city: "".to_string(),
+            coordinates: None,
+            access_address_id: Uuid::default(),
+            large_recipient_zip: "".to_string(),
+            large_recipient_city: "".to_string(),
         }
     }
 }
@@ -90,11 +88,19 @@ mod test {
             placename: "".to_string(),
             zip: "2000".to_string(),
             city: "Frederiksberg".to_string(),
+            coordinates: None,
+            access_address_id: Uuid::default(),
+            large_recipient_zip: "".to_string(),
+            large_recipient_city: "".to_string(),
         };
 
         assert_eq!(
             address.display_name(),
-            "Kronprinsesse Sofies Vej 1, Frederiksberg"
+            "Kronprinsesse Sofies Vej 1, st., 2000 Frederiksberg"
+        );
+        assert_eq!(
+            address.access_address_name(),
+            "Kronprinsesse Sofies Vej 1, 2000 Frederiksberg"
         );
     }
 }
@@ -1,23 +1,60 @@
 use std::{
-    collections::{BTreeMap, BTreeSet, BinaryHeap, HashMap},
+    collections::{btree_map, BTreeMap, BTreeSet, BinaryHeap, HashMap},
     ops::Bound,
     path::Path,
+    str::FromStr,
     sync::Arc,
     time::Instant,
 };
 
+use crate::address_formatter::AddressFormatter;
+use crate::address_parser;
+use crate::config::Settings;
+use crate::geo::{Area, Point};
+use crate::house_number::HouseNumber;
+use crate::postal_code::{PostalCode, PostalCodeIndex};
+use crate::spatial_index::SpatialIndex;
+use crate::street_index::{Street, StreetIndex};
 use crate::token_index::{DawaUuid, TokenIndex};
+use crate::unit::{Door, Floor};
+use crate::washing::{self, Candidate, Washed};
 use crate::{address::Address, size_of::SizeOf};
-use log::info;
+use log::{info, warn};
 use uuid::Uuid;
 
-const ADDRESS_FILENAME: &str = "address.csv";
-const DAWA_ADDRESS_FILENAME: &str = "../addresser.csv";
+/// The columns of the address file, as written by `convert_from_dawa_export`
+/// and `save`
+const ADDRESS_COLUMNS: [&str; 15] = [
+    "id",
+    "vejkode",
+    "kommunekode",
+    "vejnavn",
+    "husnr",
+    "etage",
+    "dør",
+    "supplerendebynavn",
+    "postnrnavn",
+    "postnr",
+    "x",
+    "y",
+    "adgangsadresseid",
+    "stormodtagerpostnr",
+    "stormodtagerpostnrnavn",
+];
+/// Up to how many prefix matches are ranked by distance directly, more are
+/// found by looking outward from the point through the spatial index
+const PROXIMITY_CANDIDATES: usize = 1000;
+/// How alike a street name must be to the washed text to be considered
+const WASH_STREET_SIMILARITY: f64 = 0.6;
 
+/// A kommune. Names and regions come from the DAWA kommune list, municipalities
+/// only seen in the address data have an empty name and region.
+#[derive(Debug)]
 pub struct Municipality {
     pub code: i32,
     pub name: String,
-    pub zip: String,
+    pub region_code: i32,
+    pub region_name: String,
 }
 
 pub struct Query {
@@ -28,6 +65,174 @@ pub struct Query {
     pub per_side: Option<i32>,
 }
 
+/// Geographic constraints on a search: `near` ranks the nearest matches
+/// first, `within` drops matches outside the area.
+#[derive(Debug, Default)]
+pub struct Proximity {
+    pub near: Option<Point>,
+    pub within: Option<Area>,
+}
+
+impl Proximity {
+    fn is_empty(&self) -> bool {
+        self.near.is_none() && self.within.is_none()
+    }
+
+    fn distance(&self, address: &Address) -> f64 {
+        match (&self.near, &address.coordinates) {
+            (Some(near), Some(point)) => near.distance(point),
+            _ => f64::INFINITY,
+        }
+    }
+}
+
+/// Structured lookup as in DAWA's `/adresser`, every field given must match.
+/// An empty `floor` or `door` matches addresses without one.
+#[derive(Debug, Default)]
+pub struct AddressFilter {
+    pub q: Option<String>,
+    pub street: Option<String>,
+    pub number: Option<String>,
+    pub floor: Option<String>,
+    pub door: Option<String>,
+    pub zip: Option<String>,
+    pub municipal_code: Option<i32>,
+    pub within: Option<Area>,
+}
+
+impl AddressFilter {
+    fn matches(&self, address: &Address, municipality: &str) -> bool {
+        self.matches_access_address(&AccessAddressRef::from(address))
+            && self.matches_unit(address, municipality)
+    }
+
+    /// The checks left for a unit once its access address matches.
+    fn matches_unit(&self, address: &Address, municipality: &str) -> bool {
+        matches_unit::<Floor>(&self.floor, &address.floor)
+            && matches_unit::<Door>(&self.door, &address.door)
+            && self.matches_text(&address.display_name(), municipality)
+    }
+
+    /// `q` is matched against the address text and the kommune name.
+    fn matches_text(&self, display_name: &str, municipality: &str) -> bool {
+        self.q
+            .as_ref()
+            .is_none_or(|q| matches_text(q, &format!("{} {}", display_name, municipality)))
+    }
+
+    fn matches_access_address(&self, access_address: &AccessAddressRef) -> bool {
+        matches_field(&self.street, access_address.street)
+            && self
+                .number
+                .as_ref()
+                .is_none_or(|number| number.parse() == Ok(access_address.number))
+            && matches_field(&self.zip, access_address.zip)
+            && self
+                .municipal_code
+                .is_none_or(|code| code == access_address.municipal_code)
+            && self.within.as_ref().is_none_or(|area| {
+                access_address
+                    .coordinates
+                    .as_ref()
+                    .is_some_and(|point| area.contains(point))
+            })
+    }
+}
+
+/// Street search as in DAWA's `/vejnavne`.
+#[derive(Debug, Default)]
+pub struct StreetFilter {
+    pub q: Option<String>,
+    pub zip: Option<String>,
+    pub municipal_code: Option<i32>,
+}
+
+impl StreetFilter {
+    fn matches(&self, street: &Street) -> bool {
+        self.q
+            .as_ref()
+            .is_none_or(|q| matches_text(q, &street.name))
+            && self
+                .zip
+                .as_ref()
+                .is_none_or(|zip| street.postal_codes.contains_key(zip))
+            && self
+                .municipal_code
+                .is_none_or(|code| street.municipal_codes.contains_key(&code))
+    }
+}
+
+/// Postal code search as in DAWA's `/postnumre`, large recipient postal
+/// codes are only included when asked for.
+#[derive(Debug, Default)]
+pub struct PostalCodeFilter {
+    pub q: Option<String>,
+    pub municipal_code: Option<i32>,
+    pub large_recipients: bool,
+}
+
+impl PostalCodeFilter {
+    fn matches(&self, postal_code: &PostalCode) -> bool {
+        (self.large_recipients || !postal_code.large_recipient)
+            && self.q.as_ref().is_none_or(|q| {
+                matches_text(q, &format!("{} {}", postal_code.nr, postal_code.name))
+            })
+            && self
+                .municipal_code
+                .is_none_or(|code| postal_code.municipal_codes.contains_key(&code))
+    }
+}
+
+/// Kommune search as in DAWA's `/kommuner`.
+#[derive(Debug, Default)]
+pub struct MunicipalityFilter {
+    pub q: Option<String>,
+    pub region_code: Option<i32>,
+}
+
+impl MunicipalityFilter {
+    fn matches(&self, municipality: &Municipality) -> bool {
+        self.q
+            .as_ref()
+            .is_none_or(|q| matches_text(q, &municipality.name))
+            && self
+                .region_code
+                .is_none_or(|code| code == municipality.region_code)
+    }
+}
+
+fn matches_field(filter: &Option<String>, value: &str) -> bool {
+    filter
+        .as_ref()
+        .is_none_or(|filter| filter.eq_ignore_ascii_case(value))
+}
+
+/// Compares floors or doors however they are spelled, `1. sal` matching `1`.
+fn matches_unit<T: FromStr + PartialEq>(filter: &Option<String>, value: &str) -> bool {
+    filter
+        .as_ref()
+        .is_none_or(|filter| match (filter.parse::<T>(), value.parse::<T>()) {
+            (Ok(filter), Ok(value)) => filter == value,
+            _ => filter.eq_ignore_ascii_case(value),
+        })
+}
+
+fn tokens(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.split(|c: char| c.is_whitespace() || c == ',' || c == '.')
+        .filter(|token| !token.is_empty())
+        .map(|token| token.to_lowercase())
+}
+
+/// Every word of the query must match a word in the text, a trailing `*`
+/// matching any word with that prefix.
+fn matches_text(query: &str, text: &str) -> bool {
+    let words: Vec<String> = tokens(text).collect();
+    tokens(query).all(|token| match token.strip_suffix('*') {
+        Some(prefix) => words.iter().any(|word| word.starts_with(prefix)),
+        None => words.contains(&token),
+    })
+}
+
 pub enum SearchMode {
     Street,
     AccessAddress,
@@ -104,12 +309,67 @@ impl AccessAddressIndicator {
 pub struct AccessAddress {
     pub name: String,
     pub municipal_code: i32,
-    pub address: BTreeMap<String, Vec<VerticalAddress>>,
+    pub address: BTreeMap<HouseNumber, Entrance>,
+}
+
+/// The access address (adgangsadresse) of one house number on a street.
+pub struct Entrance {
+    pub id: Uuid,
+    pub zip: String,
+    pub city: String,
+    pub placename: String,
+    pub large_recipient_zip: String,
+    pub large_recipient_city: String,
+    pub coordinates: Option<Point>,
+    /// In DAWA's order, by floor and then door
+    pub units: Vec<VerticalAddress>,
 }
 
+/// A unit of an access address, `None` for no floor or door.
 pub struct VerticalAddress {
-    pub floor: String,
-    pub door: String,
+    pub id: Uuid,
+    pub floor: Option<Floor>,
+    pub door: Option<Door>,
+}
+
+/// The fields shared by an access address and all of its units.
+pub struct AccessAddressRef<'a> {
+    pub id: Uuid,
+    pub street_code: i32,
+    pub municipal_code: i32,
+    pub street: &'a str,
+    pub number: HouseNumber,
+    pub zip: &'a str,
+    pub city: &'a str,
+    pub placename: &'a str,
+    pub large_recipient_zip: &'a str,
+    pub large_recipient_city: &'a str,
+    pub coordinates: Option<Point>,
+}
+
+impl AccessAddressRef<'_> {
+    pub fn display_name(&self) -> String {
+        AddressFormatter::default().format_access_address(self)
+    }
+}
+
+impl<'a> From<&'a Address> for AccessAddressRef<'a> {
+    fn from(address: &'a Address) -> Self {
+        AccessAddressRef {
+            id: address.access_address_id,
+            street_code: address.street_code,
+            municipal_code: address.municipal_code,
+            street: &address.street,
+            // Only addresses with a valid house number are indexed
+            number: address.number.parse().unwrap_or_default(),
+            zip: &address.zip,
+            city: &address.city,
+            placename: &address.placename,
+            large_recipient_zip: &address.large_recipient_zip,
+            large_recipient_city: &address.large_recipient_city,
+            coordinates: address.coordinates,
+        }
+    }
 }
 
 impl From<&AccessAddress> for Address {
@@ -125,6 +385,10 @@ impl From<&AccessAddress> for Address {
             placename: "1".to_string(),
             city: "1".to_string(),
             zip: "1".to_string(),
+            coordinates: None,
+            access_address_id: Uuid::default(),
+            large_recipient_zip: "".to_string(),
+            large_recipient_city: "".to_string(),
         }
     }
 }
@@ -137,9 +401,16 @@ pub struct AddressCompleter {
     trigrams: BTreeMap<String, Vec<AccessAddressIndicator>>,
 
     // addresses: BTreeMap<DawaUuid, Arc<Address>>,
-    addresses: BTreeMap<Uuid, Vec<Arc<Address>>>,
-    street_names: BTreeMap<String, Arc<Address>>,
+    addresses: BTreeMap<Uuid, Arc<Address>>,
+    street_names: StreetIndex,
     access_addresses: BTreeMap<String, Arc<Address>>,
+    spatial_index: SpatialIndex,
+    entrances: BTreeMap<Uuid, (AccessAddressIndicator, HouseNumber)>,
+    postal_codes: PostalCodeIndex,
+    /// Whether washing considers misspelled street names
+    fuzzy: bool,
+    /// Replacements for abbreviated words in washed text, lowercase
+    synonyms: BTreeMap<String, String>,
 }
 
 impl AddressCompleter {
@@ -151,15 +422,23 @@ impl AddressCompleter {
             trigrams: BTreeMap::new(),
             token_index: TokenIndex::new(),
             addresses: BTreeMap::new(),
-            street_names: BTreeMap::new(),
+            street_names: StreetIndex::new(),
             access_addresses: BTreeMap::new(),
+            spatial_index: SpatialIndex::new(),
+            entrances: BTreeMap::new(),
+            postal_codes: PostalCodeIndex::new(),
+            fuzzy: true,
+            synonyms: BTreeMap::new(),
         }
     }
 
-    pub fn add_address(&mut self, address: Address) {
+    /// Files an address under its street and access address, see
+    /// `insert_address` for the rest of the indexes.
+    fn add_address(&mut self, address: Address, number: HouseNumber) {
         let access_address_indicator =
             AccessAddressIndicator::new(address.municipal_code, address.street_code);
-        self.access_address
+        let units = &mut self
+            .access_address
             .entry(access_address_indicator)
             .or_insert(AccessAddress {
                 name: address.street.clone(),
@@ -167,42 +446,244 @@ impl AddressCompleter {
                 address: BTreeMap::new(),
             })
             .address
-            .entry(address.number.parse().unwrap())
-            .or_default()
-            .push(VerticalAddress {
-                floor: address.floor.parse().unwrap(),
-                door: address.door.parse().unwrap(),
-            });
+            .entry(number)
+            .or_insert_with(|| Entrance {
+                id: address.access_address_id,
+                zip: address.zip.clone(),
+                city: address.city.clone(),
+                placename: address.placename.clone(),
+                large_recipient_zip: address.large_recipient_zip.clone(),
+                large_recipient_city: address.large_recipient_city.clone(),
+                coordinates: address.coordinates,
+                units: Vec::new(),
+            })
+            .units;
+        let unit = VerticalAddress {
+            id: address.id,
+            floor: address.floor.parse().ok(),
+            door: address.door.parse().ok(),
+        };
+        let position =
+            units.partition_point(|other| (other.floor, &other.door) <= (unit.floor, &unit.door));
+        units.insert(position, unit);
+
+        self.entrances
+            .entry(address.access_address_id)
+            .or_insert((access_address_indicator, number));
 
         self.municipalities
             .entry(address.municipal_code)
             .or_insert(Municipality {
                 code: address.municipal_code,
-                name: address.city.clone(),
-                zip: address.zip.clone(),
+                name: "".to_string(),
+                region_code: 0,
+                region_name: "".to_string(),
             });
     }
 
-    pub fn init() -> AddressCompleter {
+    /// Adds an address to every index but the street name ones, which
+    /// `build_indexes` fills in once everything is loaded.
+    fn insert_address(&mut self, address: Address) -> Result<(), String> {
+        let number = address.number.parse()?;
+        self.add_address(address.clone(), number);
+        let address = Arc::new(address);
+        self.street_names.insert(&address);
+        self.postal_codes.insert(&address);
+        // Autocomplete suggests each access address once, by its first unit
+        if let btree_map::Entry::Vacant(entry) =
+            self.access_addresses.entry(access_address_key(&address))
+        {
+            entry.insert(address.clone());
+            self.spatial_index.insert(&address);
+        }
+        self.addresses.insert(address.id, address);
+        Ok(())
+    }
+
+    /// Adds an address, or replaces the one with the same id, keeping every
+    /// index up to date. Addresses without a valid house number are refused.
+    pub fn upsert_address(&mut self, address: Address) -> Result<(), String> {
+        address.number.parse::<HouseNumber>()?;
+        let indicator = AccessAddressIndicator::new(address.municipal_code, address.street_code);
+        if self
+            .access_address
+            .get(&indicator)
+            .is_some_and(|access_address| access_address.name != address.street)
+        {
+            self.rename_street(address.municipal_code, address.street_code, &address.street)?;
+        }
+        self.remove_address(&address.id);
+        let new_street = !self.access_address.contains_key(&indicator);
+        self.insert_address(address)?;
+        if new_street {
+            self.index_street(indicator);
+        }
+        Ok(())
+    }
+
+    /// Gives a street a new name, which every address on it takes, as when
+    /// DAWA renames a vejstykke.
+    pub fn rename_street(
+        &mut self,
+        municipal_code: i32,
+        street_code: i32,
+        name: &str,
+    ) -> Result<(), String> {
+        let Some(access_address) = self
+            .access_address
+            .get(&AccessAddressIndicator::new(municipal_code, street_code))
+        else {
+            return Ok(());
+        };
+        let ids: Vec<Uuid> = access_address
+            .address
+            .values()
+            .flat_map(|entrance| entrance.units.iter().map(|unit| unit.id))
+            .collect();
+        // The street goes with its last address and comes back with the new
+        // name on the first
+        let mut renamed = Vec::new();
+        for id in ids {
+            if let Some(address) = self.remove_address(&id) {
+                renamed.push(Address {
+                    street: name.to_string(),
+                    ..(*address).clone()
+                });
+            }
+        }
+        let indicator = AccessAddressIndicator::new(municipal_code, street_code);
+        for address in renamed {
+            self.insert_address(address)?;
+        }
+        self.index_street(indicator);
+        Ok(())
+    }
+
+    /// Removes an address from every index, along with its access address,
+    /// street and postal code once nothing else refers to them.
+    pub fn remove_address(&mut self, id: &Uuid) -> Option<Arc<Address>> {
+        let address = self.addresses.remove(id)?;
+        let indicator = AccessAddressIndicator::new(address.municipal_code, address.street_code);
+
+        let number: HouseNumber = address.number.parse().unwrap_or_default();
+        let mut last_unit = false;
+        if let Some(access_address) = self.access_address.get_mut(&indicator) {
+            if let Some(entrance) = access_address.address.get_mut(&number) {
+                entrance.units.retain(|unit| unit.id != address.id);
+                if entrance.units.is_empty() {
+                    access_address.address.remove(&number);
+                    self.entrances.remove(&address.access_address_id);
+                    last_unit = true;
+                }
+            }
+            if access_address.address.is_empty() {
+                self.unindex_street(indicator);
+                self.access_address.remove(&indicator);
+            }
+        }
+        self.street_names.remove(&address);
+        self.postal_codes.remove(&address, last_unit);
+
+        let key = access_address_key(&address);
+        if self
+            .access_addresses
+            .get(&key)
+            .is_some_and(|suggested| suggested.id == address.id)
+        {
+            self.access_addresses.remove(&key);
+            self.spatial_index.remove(&address);
+            let replacement = self
+                .entrance(&address.access_address_id)
+                .and_then(|(_, _, _, entrance)| entrance.units.first())
+                .and_then(|unit| self.addresses.get(&unit.id))
+                .cloned();
+            if let Some(replacement) = replacement {
+                self.spatial_index.insert(&replacement);
+                self.access_addresses.insert(key, replacement);
+            }
+        }
+        Some(address)
+    }
+
+    /// Loads the data files and applies the index options of `settings`. A
+    /// DAWA address export is converted first if there is no address file
+    /// yet.
+    pub fn init(settings: &Settings) -> AddressCompleter {
         let mut address_completer = AddressCompleter::new();
+        let municipalities = settings.data.municipalities();
+        let addresses = settings.data.addresses();
+        let dawa_addresses = settings.data.dawa_export();
+
+        address_completer.fuzzy = settings.index.fuzzy;
+        if let Some(synonyms) = &settings.index.synonyms {
+            address_completer.load_synonyms(synonyms);
+        }
 
-        if !Path::new(ADDRESS_FILENAME).exists() && Path::new(DAWA_ADDRESS_FILENAME).exists() {
+        if municipalities.exists() {
+            info!("Loading municipalities");
+            address_completer.load_municipalities(&municipalities);
+        } else {
+            warn!("{} not found, municipalities will have no names, use curl https://api.dataforsyningen.dk/kommuner?format=csv > kommuner.csv", municipalities.display());
+        }
+
+        // A newer export replaces the converted addresses
+        let modified = |path: &Path| path.metadata().and_then(|meta| meta.modified()).ok();
+        if dawa_addresses.exists() && modified(&dawa_addresses) > modified(&addresses) {
             info!("Converting addresses");
-            address_completer.convert_from_dawa_export(DAWA_ADDRESS_FILENAME, ADDRESS_FILENAME);
+            address_completer.convert_from_dawa_export(&dawa_addresses, &addresses);
         }
 
-        if Path::new(ADDRESS_FILENAME).exists() {
+        if addresses.exists() {
             info!("Loading addresses");
-            address_completer.load(ADDRESS_FILENAME);
+            address_completer.load(&addresses);
             return address_completer;
         }
-        panic!("File not found, use curl https://api.dataforsyningen.dk/adresser?format=csv > ../addresser.csv");
+        panic!(
+            "{} not found, use curl https://api.dataforsyningen.dk/adresser?format=csv > {}",
+            addresses.display(),
+            dawa_addresses.display()
+        );
+    }
+
+    pub fn address_count(&self) -> usize {
+        self.addresses.len()
+    }
+
+    /// Reads `word = replacement` lines, `#` starts a comment.
+    pub fn load_synonyms(&mut self, path: impl AsRef<Path>) {
+        let synonyms = std::fs::read_to_string(path).unwrap();
+        for line in synonyms.lines() {
+            let line = line.split('#').next().unwrap_or("");
+            if let Some((word, replacement)) = line.split_once('=') {
+                self.synonyms
+                    .insert(word.trim().to_lowercase(), replacement.trim().to_string());
+            }
+        }
+    }
+
+    fn expand_synonyms(&self, text: &str) -> String {
+        text.split_whitespace()
+            .map(|word| {
+                self.synonyms
+                    .get(&word.to_lowercase())
+                    .map_or(word, |replacement| replacement.as_str())
+            })
+            .collect::<Vec<&str>>()
+            .join(" ")
     }
 
-    pub fn convert_from_dawa_export(&mut self, path: &str, dest: &str) {
+    pub fn convert_from_dawa_export(&mut self, path: impl AsRef<Path>, dest: impl AsRef<Path>) {
         let mut rdr = csv::Reader::from_path(path).unwrap();
         let mut wrt = csv::Writer::from_path(dest).unwrap();
-        for (count, result) in rdr.records().into_iter().enumerate() {
+        let headers = rdr.headers().unwrap().clone();
+        let column = |name: &str| headers.iter().position(|header| header == name);
+        let x = column("wgs84koordinat_længde");
+        let y = column("wgs84koordinat_bredde");
+        let access_address_id = column("adgangsadresseid");
+        let large_recipient_zip = column("stormodtagerpostnr");
+        let large_recipient_city = column("stormodtagerpostnrnavn");
+        wrt.write_record(ADDRESS_COLUMNS).unwrap();
+        for (count, result) in rdr.records().enumerate() {
             let record = result.unwrap();
             wrt.write_record([
                 record.get(0).unwrap().to_string(),
@@ -215,6 +696,20 @@ impl AddressCompleter {
                 record.get(10).unwrap().to_string(),
                 record.get(12).unwrap().to_string(),
                 record.get(11).unwrap().to_string(),
+                x.and_then(|x| record.get(x)).unwrap_or("").to_string(),
+                y.and_then(|y| record.get(y)).unwrap_or("").to_string(),
+                access_address_id
+                    .and_then(|id| record.get(id))
+                    .unwrap_or("")
+                    .to_string(),
+                large_recipient_zip
+                    .and_then(|zip| record.get(zip))
+                    .unwrap_or("")
+                    .to_string(),
+                large_recipient_city
+                    .and_then(|city| record.get(city))
+                    .unwrap_or("")
+                    .to_string(),
             ])
             .unwrap();
             if (count % 300000) == 0 {
@@ -223,39 +718,69 @@ impl AddressCompleter {
         }
     }
 
-    pub fn save(&mut self, path: &str) {
-        let mut wrt = csv::Writer::from_path(path).unwrap();
-        let mut count = 0;
-        for (address_indicator, address) in self.access_address.iter() {
-            let municipality = self
-                .municipalities
-                .get(&address_indicator.municipal_code)
-                .unwrap();
-            for (number, vertical_address) in &address.address {
-                for va in vertical_address {
-                    wrt.write_record([
-                        "1",
-                        &address_indicator.street_code.to_string(),
-                        &address_indicator.municipal_code.to_string(),
-                        &address.name,
-                        number,
-                        &va.floor,
-                        &va.door,
-                        &municipality.zip,
-                        &municipality.name,
-                        "none",
-                    ])
-                    .unwrap();
-                }
-                count += 1;
-                if (count % 300000) == 0 {
-                    println!("Wrote {} addresses", count);
-                }
+    /// Reads a DAWA kommune export, `kode`, `navn`, `regionskode` and
+    /// `regionsnavn` are used.
+    pub fn load_municipalities(&mut self, path: impl AsRef<Path>) {
+        let mut rdr = csv::Reader::from_path(path).unwrap();
+        let headers = rdr.headers().unwrap().clone();
+        let column = |name: &str| headers.iter().position(|header| header == name);
+        let code = column("kode").unwrap();
+        let name = column("navn").unwrap();
+        let region_code = column("regionskode");
+        let region_name = column("regionsnavn");
+        for result in rdr.records() {
+            let record = result.unwrap();
+            let code: i32 = record.get(code).unwrap().parse().unwrap();
+            self.municipalities.insert(
+                code,
+                Municipality {
+                    code,
+                    name: record.get(name).unwrap().to_string(),
+                    region_code: region_code
+                        .and_then(|column| record.get(column))
+                        .and_then(|region_code| region_code.parse().ok())
+                        .unwrap_or_default(),
+                    region_name: region_name
+                        .and_then(|column| record.get(column))
+                        .unwrap_or("")
+                        .to_string(),
+                },
+            );
+        }
+    }
+
+    /// Writes every address to an address file that `load` reads back.
+    pub fn save(&self, path: impl AsRef<Path>) -> csv::Result<()> {
+        let mut wrt = csv::Writer::from_path(path)?;
+        wrt.write_record(ADDRESS_COLUMNS)?;
+        for (count, address) in self.addresses.values().enumerate() {
+            let coordinate = |value: Option<f64>| value.map(|value| value.to_string());
+            wrt.write_record([
+                address.id.to_string(),
+                address.street_code.to_string(),
+                address.municipal_code.to_string(),
+                address.street.clone(),
+                address.number.clone(),
+                address.floor.clone(),
+                address.door.clone(),
+                address.placename.clone(),
+                address.city.clone(),
+                address.zip.clone(),
+                coordinate(address.coordinates.map(|point| point.x)).unwrap_or_default(),
+                coordinate(address.coordinates.map(|point| point.y)).unwrap_or_default(),
+                address.access_address_id.to_string(),
+                address.large_recipient_zip.clone(),
+                address.large_recipient_city.clone(),
+            ])?;
+            if (count % 300000) == 0 {
+                info!("Wrote {} addresses", count);
             }
         }
+        wrt.flush()?;
+        Ok(())
     }
 
-    pub fn load(&mut self, path: &str) {
+    pub fn load(&mut self, path: impl AsRef<Path>) {
         let mut rdr = csv::Reader::from_path(path).unwrap();
         for (count, result) in rdr.records().enumerate() {
             let record = result.unwrap();
@@ -271,22 +796,27 @@ impl AddressCompleter {
                 placename: record.get(7).unwrap().parse().unwrap(),
                 city: record.get(8).unwrap().parse().unwrap(),
                 zip: record.get(9).unwrap().parse().unwrap(),
+                coordinates: match (record.get(10), record.get(11)) {
+                    (Some(x), Some(y)) => match (x.parse(), y.parse()) {
+                        (Ok(x), Ok(y)) => Some(Point::new(x, y)),
+                        _ => None,
+                    },
+                    _ => None,
+                },
+                access_address_id: record
+                    .get(12)
+                    .and_then(|id| id.parse().ok())
+                    .unwrap_or_default(),
+                large_recipient_zip: record.get(13).unwrap_or("").to_string(),
+                large_recipient_city: record.get(14).unwrap_or("").to_string(),
             };
             // println!("{}", address.display_name());
-            self.add_address(address.clone());
             let dawa_uuid = DawaUuid::new(record.get(0).unwrap().parse().unwrap());
-            let aaddress = Arc::new(address);
+            let id = address.id;
+            if let Err(error) = self.insert_address(address) {
+                warn!("Skipping address {}: {}", id, error);
+            }
             // self.addresses.insert(dawa_uuid.clone(), aaddress.clone());
-            self.street_names
-                .insert(street.to_lowercase(), aaddress.clone());
-            self.access_addresses.insert(
-                aaddress.access_address_name().to_lowercase(),
-                aaddress.clone(),
-            );
-            self.addresses
-                .entry(aaddress.id)
-                .or_insert(Vec::new())
-                .push(aaddress.clone());
             // let a = self.addresses.get(&dawa_uuid).unwrap();
             // self.token_index.insert(a.display_name(), a);
             // self.addresses.insert(count, address);
@@ -324,48 +854,530 @@ impl AddressCompleter {
         // }
     }
 
-    pub fn find_access_address(&self, display_name: String, count: i32) -> Vec<Arc<Address>> {
-        let mut result = Vec::new();
+    pub fn find_access_address(
+        &self,
+        display_name: String,
+        count: i32,
+        proximity: &Proximity,
+    ) -> Vec<Arc<Address>> {
+        let prefix = display_name.to_lowercase();
+        let count = count.max(0) as usize;
+        if proximity.is_empty() {
+            // Those starting with the text in natural order, street by street,
+            // the streets the text runs into the house number of first
+            let streets = prefix
+                .match_indices(' ')
+                .filter_map(|(end, _)| self.street_names.get(&prefix[..end]))
+                .chain(self.street_names.prefix(&prefix));
+            let mut result = Vec::new();
+            for street in streets {
+                if result.len() >= count {
+                    break;
+                }
+                let addresses = self
+                    .street_access_addresses(&street.name.to_lowercase())
+                    .into_iter()
+                    .filter(|address| access_address_key(address).starts_with(&prefix));
+                result.extend(addresses.take(count - result.len()).cloned());
+            }
+            // Then whatever follows the text alphabetically
+            let mut cursor = self.access_addresses.lower_bound(Bound::Included(&prefix));
+            while let Some((name, _)) = cursor.peek_next() {
+                if !name.starts_with(&prefix) {
+                    break;
+                }
+                cursor.next();
+            }
+            while result.len() < count {
+                match cursor.next() {
+                    Some((_, address)) => result.push(address.clone()),
+                    None => break,
+                }
+            }
+            return result;
+        }
 
-        let mut cursor = self
-            .access_addresses
-            .lower_bound(Bound::Included(&display_name.to_lowercase()));
-        for _ in 0..count {
-            if let Some((_, address)) = cursor.next() {
-                result.push(address.clone());
+        match (&proximity.within, proximity.near) {
+            (Some(area), _) => {
+                let mut result: Vec<Arc<Address>> = self
+                    .spatial_index
+                    .within(area)
+                    .filter(|address| access_address_key(address).starts_with(&prefix))
+                    .cloned()
+                    .collect();
+                result.sort_by_cached_key(|address| natural_order(address));
+                if proximity.near.is_some() {
+                    result.sort_by(|a, b| proximity.distance(a).total_cmp(&proximity.distance(b)));
+                }
+                result.truncate(count);
+                result
             }
+            (None, Some(near)) => self
+                .nearest_access_addresses(&prefix, near)
+                .take(count)
+                .cloned()
+                .collect(),
+            (None, None) => Vec::new(),
         }
+    }
+
+    /// The access addresses on a street, given in lowercase, in natural
+    /// order.
+    fn street_access_addresses(&self, street: &str) -> Vec<&Arc<Address>> {
+        let start = format!("{} ", street);
+        let mut result: Vec<&Arc<Address>> = self
+            .access_addresses
+            .range(start.clone()..)
+            .take_while(|(name, _)| name.starts_with(&start))
+            .map(|(_, address)| address)
+            .filter(|address| address.street.to_lowercase() == street)
+            .collect();
+        result.sort_by_cached_key(|address| natural_order(address));
         result
     }
 
+    /// The access addresses starting with `prefix`, nearest `point` first.
+    /// A few are ranked directly, while many are found by looking outward
+    /// from the point so that the nearest is found however far down the
+    /// alphabet it is.
+    fn nearest_access_addresses<'a>(
+        &'a self,
+        prefix: &'a str,
+        point: Point,
+    ) -> Box<dyn Iterator<Item = &'a Arc<Address>> + 'a> {
+        let mut matches: Vec<&Arc<Address>> = self
+            .access_addresses
+            .range(prefix.to_string()..)
+            .take_while(|(name, _)| name.starts_with(prefix))
+            .take(PROXIMITY_CANDIDATES + 1)
+            .map(|(_, address)| address)
+            .collect();
+        if matches.len() > PROXIMITY_CANDIDATES {
+            return Box::new(
+                self.spatial_index
+                    .nearest(point)
+                    .filter(move |address| access_address_key(address).starts_with(prefix)),
+            );
+        }
+        let distance = |address: &Address| {
+            address
+                .coordinates
+                .map_or(f64::INFINITY, |other| point.distance(&other))
+        };
+        matches.sort_by(|a, b| distance(a).total_cmp(&distance(b)));
+        Box::new(matches.into_iter())
+    }
+
+    /// The units of an access address, kælder and stuen first and `tv`
+    /// before `mf` before `th`. A floor or door in the text, such as
+    /// `1 sal th` or `st. tv`, narrows them down.
     pub fn find_address(
         &self,
-        display_name: &String,
-        access_address_id: &Option<String>,
+        display_name: &str,
+        access_address_id: &Uuid,
         count: i32,
     ) -> Vec<Arc<Address>> {
-        let mut result = Vec::new();
+        let Some((_, _, _, entrance)) = self.entrance(access_address_id) else {
+            return Vec::new();
+        };
+        let parsed = address_parser::parse(display_name);
+        entrance
+            .units
+            .iter()
+            .filter(|unit| {
+                parsed.floor.is_none_or(|floor| unit.floor == Some(floor))
+                    && parsed
+                        .door
+                        .as_ref()
+                        .is_none_or(|door| unit.door.as_ref() == Some(door))
+            })
+            .filter_map(|unit| self.addresses.get(&unit.id))
+            .take(count.max(0) as usize)
+            .cloned()
+            .collect()
+    }
+
+    pub fn address(&self, id: &Uuid) -> Option<&Arc<Address>> {
+        self.addresses.get(id)
+    }
+
+    fn entrance(
+        &self,
+        id: &Uuid,
+    ) -> Option<(
+        AccessAddressIndicator,
+        &AccessAddress,
+        HouseNumber,
+        &Entrance,
+    )> {
+        let (indicator, number) = self.entrances.get(id)?;
+        let access_address = self.access_address.get(indicator)?;
+        let entrance = access_address.address.get(number)?;
+        Some((*indicator, access_address, *number, entrance))
+    }
+
+    pub fn access_address(&self, id: &Uuid) -> Option<AccessAddressRef<'_>> {
+        self.entrance(id)
+            .map(|(indicator, access_address, number, entrance)| {
+                Self::access_address_ref(indicator, access_address, number, entrance)
+            })
+    }
+
+    /// The units of an access address, kælder and stuen first.
+    pub fn units(&self, access_address_id: &Uuid) -> Vec<Arc<Address>> {
+        self.entrance(access_address_id)
+            .map(|(_, _, _, entrance)| {
+                entrance
+                    .units
+                    .iter()
+                    .filter_map(|unit| self.addresses.get(&unit.id))
+                    .cloned()
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// The name of the street with this kommunekode and vejkode.
+    pub fn street_name(&self, municipal_code: i32, street_code: i32) -> Option<&str> {
+        self.access_address
+            .get(&AccessAddressIndicator::new(municipal_code, street_code))
+            .map(|access_address| access_address.name.as_str())
+    }
+
+    fn access_address_ref<'a>(
+        indicator: AccessAddressIndicator,
+        access_address: &'a AccessAddress,
+        number: HouseNumber,
+        entrance: &'a Entrance,
+    ) -> AccessAddressRef<'a> {
+        AccessAddressRef {
+            id: entrance.id,
+            street_code: indicator.street_code,
+            municipal_code: indicator.municipal_code,
+            street: &access_address.name,
+            number,
+            zip: &entrance.zip,
+            city: &entrance.city,
+            placename: &entrance.placename,
+            large_recipient_zip: &entrance.large_recipient_zip,
+            large_recipient_city: &entrance.large_recipient_city,
+            coordinates: entrance.coordinates,
+        }
+    }
+
+    /// The streets an address filter can match, narrowed through the street,
+    /// postal code and kommune indexes to the kommuner that can hold a match.
+    fn filtered_streets<'a>(
+        &'a self,
+        filter: &'a AddressFilter,
+    ) -> impl Iterator<Item = (AccessAddressIndicator, &'a AccessAddress)> + 'a {
+        let municipal_codes = [
+            filter.municipal_code.map(|code| BTreeSet::from([code])),
+            filter.street.as_ref().map(|name| {
+                self.street_names
+                    .get(name)
+                    .map_or_else(BTreeSet::new, |street| {
+                        street.municipal_codes.keys().copied().collect()
+                    })
+            }),
+            filter.zip.as_ref().map(|zip| {
+                self.postal_code(zip)
+                    .map_or_else(BTreeSet::new, |postal_code| {
+                        postal_code.municipal_codes.keys().copied().collect()
+                    })
+            }),
+        ]
+        .into_iter()
+        .flatten()
+        .reduce(|codes, other| &codes & &other);
+        let streets: Box<dyn Iterator<Item = (&AccessAddressIndicator, &AccessAddress)>> =
+            match municipal_codes {
+                Some(codes) => Box::new(codes.into_iter().flat_map(|code| {
+                    self.access_address.range(
+                        AccessAddressIndicator::new(code, i32::MIN)
+                            ..=AccessAddressIndicator::new(code, i32::MAX),
+                    )
+                })),
+                None => Box::new(self.access_address.iter()),
+            };
+        streets
+            .filter(|(_, access_address)| matches_field(&filter.street, &access_address.name))
+            .map(|(indicator, access_address)| (*indicator, access_address))
+    }
+
+    /// Access addresses matching everything but `q`, looking a house number
+    /// up on each street rather than comparing every entrance.
+    fn filtered_access_addresses<'a>(
+        &'a self,
+        filter: &'a AddressFilter,
+    ) -> impl Iterator<Item = (AccessAddressRef<'a>, &'a Entrance)> + 'a {
+        let number = filter
+            .number
+            .as_ref()
+            .map(|number| number.parse::<HouseNumber>().ok());
+        self.filtered_streets(filter)
+            .flat_map(move |(indicator, access_address)| {
+                let entrances: Box<dyn Iterator<Item = (&HouseNumber, &Entrance)>> = match number {
+                    Some(number) => Box::new(
+                        number
+                            .and_then(|number| access_address.address.get_key_value(&number))
+                            .into_iter(),
+                    ),
+                    None => Box::new(access_address.address.iter()),
+                };
+                entrances.map(move |(number, entrance)| {
+                    (
+                        Self::access_address_ref(indicator, access_address, *number, entrance),
+                        entrance,
+                    )
+                })
+            })
+            .filter(|(access_address, _)| filter.matches_access_address(access_address))
+    }
+
+    /// Access addresses ordered by municipality, street and house number.
+    pub fn search_access_addresses<'a>(
+        &'a self,
+        filter: &'a AddressFilter,
+    ) -> impl Iterator<Item = AccessAddressRef<'a>> + 'a {
+        self.filtered_access_addresses(filter)
+            .map(|(access_address, _)| access_address)
+            .filter(|access_address| {
+                filter.matches_text(
+                    &access_address.display_name(),
+                    self.municipality_name(access_address.municipal_code),
+                )
+            })
+    }
+
+    /// Addresses ordered by their access address, then as the units were
+    /// added.
+    pub fn search_addresses<'a>(
+        &'a self,
+        filter: &'a AddressFilter,
+    ) -> impl Iterator<Item = &'a Arc<Address>> + 'a {
+        self.filtered_access_addresses(filter)
+            .flat_map(|(_, entrance)| entrance.units.iter())
+            .filter_map(|unit| self.addresses.get(&unit.id))
+            .filter(|address| {
+                filter.matches_unit(address, self.municipality_name(address.municipal_code))
+            })
+    }
 
-        let uuid: Uuid = Uuid::parse_str(access_address_id.as_ref().unwrap().as_str()).unwrap();
-        let cursor = self.addresses.get(&uuid);
-        if let Some(cursor) = cursor {
-            for a in cursor {
-                result.push(a.clone());
+    /// Looks at up to `count` addresses in id order, starting after `after`,
+    /// and returns those matching the filter along with the id to continue
+    /// after, `None` when every address has been looked at.
+    pub fn scan_addresses(
+        &self,
+        filter: &AddressFilter,
+        after: Option<Uuid>,
+        count: usize,
+    ) -> (Vec<Arc<Address>>, Option<Uuid>) {
+        let start = after.map_or(Bound::Unbounded, Bound::Excluded);
+        let mut last = None;
+        let mut matches = Vec::new();
+        for (id, address) in self.addresses.range((start, Bound::Unbounded)).take(count) {
+            last = Some(*id);
+            if filter.matches(address, self.municipality_name(address.municipal_code)) {
+                matches.push(address.clone());
             }
         }
-        result
+        (matches, last)
+    }
+
+    /// Finds the addresses best matching a free text address, allowing for
+    /// misspellings, as in DAWA's `/datavask/adresser`.
+    pub fn wash(&self, text: &str) -> Washed {
+        let mut parsed = address_parser::parse(text);
+        parsed.street = parsed.street.map(|street| self.expand_synonyms(&street));
+        let street = parsed.street.clone().unwrap_or_default();
+        let required_similarity = if self.fuzzy {
+            WASH_STREET_SIMILARITY
+        } else {
+            1.0
+        };
+        let mut candidates: Vec<Candidate> = self
+            .street_candidates(&street)
+            .iter()
+            .filter_map(|indicator| self.access_address.get(indicator))
+            .filter(|access_address| {
+                washing::similarity(&street, &access_address.name) >= required_similarity
+            })
+            .flat_map(|access_address| access_address.address.values())
+            .flat_map(|entrance| entrance.units.iter())
+            .filter_map(|unit| self.addresses.get(&unit.id))
+            .map(|address| washing::compare(&parsed, address))
+            .collect();
+        let category = washing::categorize(&mut candidates);
+        Washed {
+            category,
+            parsed,
+            candidates,
+        }
+    }
+
+    /// The streets sharing at least half the trigrams of `street`, the only
+    /// ones worth comparing with it. Keeps washing a row from measuring the
+    /// distance to every street in the country.
+    fn street_candidates(&self, street: &str) -> BTreeSet<AccessAddressIndicator> {
+        let wanted: BTreeSet<String> = trigrams(street).into_iter().collect();
+        let mut shared: HashMap<AccessAddressIndicator, usize> = HashMap::new();
+        for trigram in &wanted {
+            for indicator in self.trigrams.get(trigram).into_iter().flatten() {
+                *shared.entry(*indicator).or_default() += 1;
+            }
+        }
+        shared
+            .into_iter()
+            .filter(|(_, count)| count * 2 >= wanted.len())
+            .map(|(indicator, _)| indicator)
+            .collect()
     }
 
-    pub fn find_street(&self, display_name: String, count: i32) -> Vec<String> {
+    pub fn municipality(&self, code: i32) -> Option<&Municipality> {
+        self.municipalities.get(&code)
+    }
+
+    /// The kommune name, empty when the kommune list was not loaded.
+    pub fn municipality_name(&self, code: i32) -> &str {
+        self.municipalities
+            .get(&code)
+            .map_or("", |municipality| municipality.name.as_str())
+    }
+
+    pub fn search_municipalities<'a>(
+        &'a self,
+        filter: &'a MunicipalityFilter,
+    ) -> impl Iterator<Item = &'a Municipality> + 'a {
+        self.municipalities
+            .values()
+            .filter(|municipality| filter.matches(municipality))
+    }
+
+    /// Kommuner whose name or code starts with `prefix`.
+    pub fn complete_municipalities<'a>(
+        &'a self,
+        prefix: &str,
+        filter: &'a MunicipalityFilter,
+    ) -> impl Iterator<Item = &'a Municipality> + 'a {
+        let prefix = prefix.to_lowercase();
+        self.search_municipalities(filter)
+            .filter(move |municipality| {
+                municipality.name.to_lowercase().starts_with(&prefix)
+                    || format!("{:04}", municipality.code).starts_with(&prefix)
+            })
+    }
+
+    pub fn street(&self, name: &str) -> Option<&Street> {
+        self.street_names.get(name)
+    }
+
+    pub fn search_streets<'a>(
+        &'a self,
+        filter: &'a StreetFilter,
+    ) -> impl Iterator<Item = &'a Street> + 'a {
+        self.street_names
+            .iter()
+            .filter(|street| filter.matches(street))
+    }
+
+    /// Streets starting with `prefix`, in alphabetical order.
+    pub fn complete_streets<'a>(
+        &'a self,
+        prefix: &str,
+        filter: &'a StreetFilter,
+    ) -> impl Iterator<Item = &'a Street> + 'a {
+        self.street_names
+            .prefix(prefix)
+            .filter(|street| filter.matches(street))
+    }
+
+    pub fn postal_code(&self, nr: &str) -> Option<&PostalCode> {
+        self.postal_codes.get(nr)
+    }
+
+    pub fn search_postal_codes<'a>(
+        &'a self,
+        filter: &'a PostalCodeFilter,
+    ) -> impl Iterator<Item = &'a PostalCode> + 'a {
+        self.postal_codes
+            .iter()
+            .filter(|postal_code| filter.matches(postal_code))
+    }
+
+    /// Postal codes whose number or name starts with `prefix`.
+    pub fn complete_postal_codes<'a>(
+        &'a self,
+        prefix: &str,
+        filter: &'a PostalCodeFilter,
+    ) -> impl Iterator<Item = &'a PostalCode> + 'a {
+        let prefix = prefix.to_lowercase();
+        self.search_postal_codes(filter).filter(move |postal_code| {
+            postal_code.nr.starts_with(&prefix)
+                || postal_code.name.to_lowercase().starts_with(&prefix)
+                || format!("{} {}", postal_code.nr, postal_code.name.to_lowercase())
+                    .starts_with(&prefix)
+        })
+    }
+
+    pub fn find_street(
+        &self,
+        display_name: String,
+        count: i32,
+        proximity: &Proximity,
+    ) -> Vec<String> {
         let mut result = Vec::new();
+        let prefix = display_name.to_lowercase();
 
-        let mut cursor = self
-            .street_names
-            .lower_bound(Bound::Included(&display_name.to_lowercase()));
-        for _ in 0..count {
-            if let Some((_, address)) = cursor.next() {
-                result.push(address.street.clone() + " ");
+        if let Some(area) = &proximity.within {
+            // The street's entrance nearest the point, or its name when there
+            // is no point
+            let mut streets: BTreeMap<String, (f64, &str)> = BTreeMap::new();
+            for address in self.spatial_index.within(area) {
+                let name = address.street.to_lowercase();
+                if name.starts_with(&prefix) {
+                    let distance = proximity.distance(address);
+                    let street = streets.entry(name).or_insert((distance, &address.street));
+                    street.0 = street.0.min(distance);
+                }
+            }
+            let mut streets: Vec<(f64, &str)> = streets.into_values().collect();
+            if proximity.near.is_some() {
+                streets.sort_by(|a, b| a.0.total_cmp(&b.0));
+            }
+            for (_, street) in streets.into_iter().take(count.max(0) as usize) {
+                result.push(street.to_string() + " ");
+            }
+            return result;
+        }
+
+        if let Some(near) = proximity.near {
+            // Streets by their entrance nearest the point, stopping once
+            // every street there is has been found
+            let count = self
+                .street_names
+                .prefix(&prefix)
+                .take(count.max(0) as usize)
+                .count();
+            let mut found = BTreeSet::new();
+            for address in self.nearest_access_addresses(&prefix, near) {
+                if result.len() >= count {
+                    break;
+                }
+                let name = address.street.to_lowercase();
+                if name.starts_with(&prefix) && found.insert(name) {
+                    result.push(address.street.clone() + " ");
+                }
             }
+            return result;
+        }
+
+        for street in self
+            .street_names
+            .range_from(&display_name)
+            .take(count.max(0) as usize)
+        {
+            result.push(street.name.clone() + " ");
         }
 
         // let mut trigram_matches = HashMap::new();
@@ -403,22 +1415,12 @@ impl AddressCompleter {
             self.index
                 .insert(access_address.name.clone().to_lowercase(), *aai);
 
-            for w in access_address
-                .name
-                .clone()
-                .to_lowercase()
-                .into_bytes()
-                .windows(3)
-            {
-                let trigram = String::from_utf8_lossy(w);
-                self.trigrams
-                    .entry(trigram.to_string())
-                    .or_default()
-                    .push(*aai);
+            for trigram in trigrams(&access_address.name) {
+                self.trigrams.entry(trigram).or_default().push(*aai);
             }
 
             if (count % 300000) == 0 {
-                println!("Indexed {} addresses", count);
+                info!("Indexed {} addresses", count);
             }
         }
         let elapsed = start.elapsed();
@@ -427,6 +1429,86 @@ impl AddressCompleter {
     }
 }
 
+impl AddressCompleter {
+    /// Adds a street that was not there when `build_indexes` ran.
+    fn index_street(&mut self, indicator: AccessAddressIndicator) {
+        let Some(access_address) = self.access_address.get(&indicator) else {
+            return;
+        };
+        self.index
+            .insert(access_address.name.to_lowercase(), indicator);
+        for trigram in trigrams(&access_address.name) {
+            self.trigrams.entry(trigram).or_default().push(indicator);
+        }
+    }
+
+    fn unindex_street(&mut self, indicator: AccessAddressIndicator) {
+        let Some(access_address) = self.access_address.get(&indicator) else {
+            return;
+        };
+        let name = access_address.name.to_lowercase();
+        if self.index.get(&name) == Some(&indicator) {
+            // Streets in other municipalities may have the same name
+            match self.access_address.iter().find(|(other, access_address)| {
+                **other != indicator && access_address.name.to_lowercase() == name
+            }) {
+                Some((other, _)) => self.index.insert(name, *other),
+                None => self.index.remove(&name),
+            };
+        }
+        for trigram in trigrams(&access_address.name) {
+            if let Some(indicators) = self.trigrams.get_mut(&trigram) {
+                indicators.retain(|other| *other != indicator);
+                if indicators.is_empty() {
+                    self.trigrams.remove(&trigram);
+                }
+            }
+        }
+    }
+}
+
+/// Orders access addresses by street, then house number as a number, so `2`
+/// comes before `10`.
+fn natural_order(address: &Address) -> (String, HouseNumber, String) {
+    (
+        address.street.to_lowercase(),
+        address.number.parse().unwrap_or_default(),
+        access_address_key(address),
+    )
+}
+
+/// What access addresses are completed on: the `betegnelse` in lowercase
+/// without the supplerende bynavn, so `hovedgaden 1, 8210` still finds the
+/// one in Brabrand.
+fn access_address_key(address: &Address) -> String {
+    format!(
+        "{} {}, {} {}",
+        address.street, address.number, address.zip, address.city
+    )
+    .to_lowercase()
+}
+
+/// The three byte windows of a lowercase street name.
+fn trigrams(name: &str) -> Vec<String> {
+    name.to_lowercase()
+        .into_bytes()
+        .windows(3)
+        .map(|window| String::from_utf8_lossy(window).to_string())
+        .collect()
+}
+
+/// The addresses and municipalities in `fixtures`, for tests.
+#[cfg(test)]
+pub(crate) fn fixture() -> AddressCompleter {
+    let mut address_completer = AddressCompleter::new();
+    address_completer.load_municipalities(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/fixtures/kommuner.csv"
+    ));
+    address_completer.load(concat!(env!("CARGO_MANIFEST_DIR"), "/fixtures/address.csv"));
+    address_completer
+}
+
 /**
  * Strategy:
  *   Stemming?
@@ -439,13 +1521,703 @@ mod tests {
     use super::*;
     use once_cell::sync::Lazy;
 
-    static ADDRESS_COMPLETER: Lazy<AddressCompleter> = Lazy::new(AddressCompleter::init);
+    static ADDRESS_COMPLETER: Lazy<AddressCompleter> =
+        Lazy::new(|| AddressCompleter::init(&Settings::default()));
+
+    #[test]
+    fn test_find_address() {
+        let address_completer = fixture();
+        let id = "0a3f507a-c086-32b8-e044-0003ba298018".parse().unwrap();
+        let doors = |text: &str| -> Vec<String> {
+            address_completer
+                .find_address(text, &id, 10)
+                .iter()
+                .map(|address| format!("{} {}", address.floor, address.door))
+                .collect()
+        };
+        assert_eq!(doors("Maribovej 15"), ["st tv", "st th", "1 tv"]);
+        assert_eq!(doors("Maribovej 15, st. tv"), ["st tv"]);
+        assert_eq!(doors("maribovej 15 1 sal t.v."), ["1 tv"]);
+        assert_eq!(doors("Maribovej 15 stuen"), ["st tv", "st th"]);
+        assert!(doors("Maribovej 15, 2. th").is_empty());
+        assert_eq!(
+            address_completer.find_address("Maribovej 15", &id, 1).len(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_find_access_address_near() {
+        let address_completer = fixture();
+        let roskilde = Point::new(12.08, 55.64);
+        let proximity = Proximity {
+            near: Some(roskilde),
+            within: None,
+        };
+        let result =
+            address_completer.find_access_address("Hovedgaden 1".to_string(), 3, &proximity);
+        let cities: Vec<&str> = result.iter().map(|a| a.city.as_str()).collect();
+        assert_eq!(cities, ["Hedehusene", "Svinninge", "Aarhus V"]);
+    }
+
+    #[test]
+    fn test_find_access_address_without_placename() {
+        let address_completer = fixture();
+        for q in ["Hovedgaden 1, 8210", "hovedgaden 1, 8210 aarhus v"] {
+            let result =
+                address_completer.find_access_address(q.to_string(), 1, &Proximity::default());
+            assert_eq!(result[0].placename, "Brabrand", "{}", q);
+            assert_eq!(
+                result[0].access_address_name(),
+                "Hovedgaden 1, Brabrand, 8210 Aarhus V"
+            );
+        }
+    }
+
+    #[test]
+    fn test_find_access_address_natural_order() {
+        let address_completer = fixture();
+        let result = address_completer.find_access_address(
+            "maribovej".to_string(),
+            5,
+            &Proximity::default(),
+        );
+        let numbers: Vec<&str> = result.iter().map(|a| a.number.as_str()).collect();
+        assert_eq!(numbers, ["1", "2", "3", "10", "15"]);
+
+        let proximity = Proximity {
+            near: None,
+            within: Some(Area::Circle("11.457,54.712,1000".parse().unwrap())),
+        };
+        let result = address_completer.find_access_address("maribovej".to_string(), 10, &proximity);
+        let numbers: Vec<&str> = result.iter().map(|a| a.number.as_str()).collect();
+        assert_eq!(numbers, ["1", "2", "3", "10"]);
+    }
+
+    /// More access addresses on one street than are ranked by distance
+    /// directly, and a short street west of it.
+    fn long_street() -> AddressCompleter {
+        let mut address_completer = AddressCompleter::new();
+        let streets = (1..=1200)
+            .map(|number| ("Testvej", number, 11.0 + number as f64 * 0.001))
+            .chain([("Testgade", 1, 10.99)]);
+        for (id, (street, number, x)) in streets.enumerate() {
+            let id = id as u128 + 1;
+            address_completer
+                .insert_address(Address {
+                    id: Uuid::from_u128(id),
+                    access_address_id: Uuid::from_u128(10_000 + id),
+                    street_code: if street == "Testvej" { 1 } else { 2 },
+                    municipal_code: 360,
+                    street: street.to_string(),
+                    number: number.to_string(),
+                    zip: "4960".to_string(),
+                    city: "Holeby".to_string(),
+                    coordinates: Some(Point::new(x, 54.7)),
+                    ..Address::default()
+                })
+                .unwrap();
+        }
+        address_completer
+    }
+
+    #[test]
+    fn test_find_access_address_long_street() {
+        let address_completer = long_street();
+        let result = address_completer.find_access_address(
+            "testvej".to_string(),
+            1200,
+            &Proximity::default(),
+        );
+        let numbers: Vec<usize> = result.iter().map(|a| a.number.parse().unwrap()).collect();
+        assert_eq!(numbers, (1..=1200).collect::<Vec<_>>());
+
+        // `testvej 999` comes last alphabetically
+        let proximity = Proximity {
+            near: Some(Point::new(11.9991, 54.7)),
+            within: None,
+        };
+        let result = address_completer.find_access_address("testvej".to_string(), 2, &proximity);
+        let numbers: Vec<&str> = result.iter().map(|a| a.number.as_str()).collect();
+        assert_eq!(numbers, ["999", "1000"]);
+        let result = address_completer.find_access_address("test".to_string(), 1, &proximity);
+        assert_eq!(result[0].number, "999");
+    }
+
+    #[test]
+    fn test_find_street_near() {
+        let address_completer = long_street();
+        let near = |x| Proximity {
+            near: Some(Point::new(x, 54.7)),
+            within: None,
+        };
+        assert_eq!(
+            address_completer.find_street("test".to_string(), 10, &near(12.0)),
+            ["Testvej ", "Testgade "]
+        );
+        assert_eq!(
+            address_completer.find_street("test".to_string(), 10, &near(10.9)),
+            ["Testgade ", "Testvej "]
+        );
+        assert_eq!(
+            address_completer.find_street("testg".to_string(), 10, &near(12.0)),
+            ["Testgade "]
+        );
+
+        let proximity = Proximity {
+            near: Some(Point::new(12.0, 54.7)),
+            within: Some(Area::Circle("11.0,54.7,100000".parse().unwrap())),
+        };
+        assert_eq!(
+            address_completer.find_street("test".to_string(), 10, &proximity),
+            ["Testvej ", "Testgade "]
+        );
+    }
+
+    #[test]
+    fn test_find_access_address_within() {
+        let address_completer = fixture();
+        let proximity = Proximity {
+            near: None,
+            within: Some(Area::Circle("11.46,55.72,5000".parse().unwrap())),
+        };
+        let result =
+            address_completer.find_access_address("hovedgaden".to_string(), 10, &proximity);
+        assert_eq!(result.len(), 1);
+        assert_eq!(result[0].city, "Svinninge");
+    }
 
     #[test]
-    fn test_find_address() {}
+    fn test_search_addresses() {
+        let address_completer = fixture();
+        let filter = AddressFilter {
+            street: Some("maribovej".to_string()),
+            number: Some("15".to_string()),
+            floor: Some("st".to_string()),
+            ..AddressFilter::default()
+        };
+        let result: Vec<_> = address_completer.search_addresses(&filter).collect();
+        assert_eq!(result.len(), 2);
+
+        let filter = AddressFilter {
+            q: Some("maribovej 15 1 tv".to_string()),
+            ..AddressFilter::default()
+        };
+        let result: Vec<_> = address_completer.search_addresses(&filter).collect();
+        assert_eq!(result.len(), 1);
+        assert_eq!(result[0].floor, "1");
+
+        let filter = AddressFilter {
+            q: Some("mari* 15".to_string()),
+            ..AddressFilter::default()
+        };
+        assert_eq!(address_completer.search_addresses(&filter).count(), 3);
+
+        let filter = AddressFilter {
+            floor: Some("".to_string()),
+            municipal_code: Some(360),
+            ..AddressFilter::default()
+        };
+        assert_eq!(address_completer.search_addresses(&filter).count(), 4);
+    }
+
+    #[test]
+    fn test_search_addresses_narrowed() {
+        let address_completer = fixture();
+        let filter = |street: &str, number: &str, zip: &str, municipal_code| AddressFilter {
+            street: Some(street.to_string()).filter(|street| !street.is_empty()),
+            number: Some(number.to_string()).filter(|number| !number.is_empty()),
+            zip: Some(zip.to_string()).filter(|zip| !zip.is_empty()),
+            municipal_code,
+            ..AddressFilter::default()
+        };
+        for filter in [
+            filter("MARIBOVEJ", "15", "", None),
+            filter("maribovej", "", "4960", None),
+            filter("maribovej", "", "", Some(101)),
+            filter("maribovej", "", "2500", Some(360)),
+            filter("hovedgaden", "1", "", None),
+            filter("", "", "2500", None),
+            filter("", "1", "", Some(360)),
+            filter("", "x", "", None),
+            filter("ukendt vej", "", "", None),
+        ] {
+            let mut expected: Vec<Uuid> = address_completer
+                .addresses
+                .values()
+                .filter(|address| filter.matches(address, ""))
+                .map(|address| address.id)
+                .collect();
+            let mut found: Vec<Uuid> = address_completer
+                .search_addresses(&filter)
+                .map(|address| address.id)
+                .collect();
+            expected.sort();
+            found.sort();
+            assert_eq!(found, expected, "{:?}", filter);
+        }
+        assert_eq!(
+            address_completer
+                .search_addresses(&filter("maribovej", "15", "2500", None))
+                .count(),
+            3
+        );
+    }
+
+    #[test]
+    fn test_search_access_addresses_narrowed() {
+        let address_completer = fixture();
+        for (street, number) in [("Maribovej", "2"), ("hovedgaden", "1"), ("maribovej", "2x")] {
+            let filter = AddressFilter {
+                street: Some(street.to_string()),
+                number: Some(number.to_string()),
+                ..AddressFilter::default()
+            };
+            let expected: Vec<Uuid> = address_completer
+                .access_address
+                .iter()
+                .flat_map(|(indicator, access_address)| {
+                    access_address.address.iter().map(|(number, entrance)| {
+                        AddressCompleter::access_address_ref(
+                            *indicator,
+                            access_address,
+                            *number,
+                            entrance,
+                        )
+                    })
+                })
+                .filter(|access_address| filter.matches_access_address(access_address))
+                .map(|access_address| access_address.id)
+                .collect();
+            let found: Vec<Uuid> = address_completer
+                .search_access_addresses(&filter)
+                .map(|access_address| access_address.id)
+                .collect();
+            assert_eq!(found, expected, "{:?}", filter);
+        }
+    }
+
+    #[test]
+    fn test_scan_addresses() {
+        let address_completer = fixture();
+        let filter = AddressFilter {
+            zip: Some("2500".to_string()),
+            ..AddressFilter::default()
+        };
+        let mut found = Vec::new();
+        let mut after = None;
+        let mut chunks = 0;
+        loop {
+            let (addresses, last) = address_completer.scan_addresses(&filter, after, 5);
+            found.extend(addresses.iter().map(|address| address.id));
+            chunks += 1;
+            match last {
+                Some(last) => after = Some(last),
+                None => break,
+            }
+        }
+        assert_eq!(chunks, 4);
+        let mut expected: Vec<Uuid> = address_completer
+            .search_addresses(&filter)
+            .map(|address| address.id)
+            .collect();
+        expected.sort();
+        assert_eq!(found, expected);
+        assert_eq!(found.len(), 3);
+    }
+
+    #[test]
+    fn test_access_address_lookup() {
+        let address_completer = fixture();
+        let id = "0a3f507a-c086-32b8-e044-0003ba298018".parse().unwrap();
+        let access_address = address_completer.access_address(&id).unwrap();
+        assert_eq!(access_address.display_name(), "Maribovej 15, 2500 Valby");
+        assert_eq!(access_address.street_code, 4640);
+
+        let units = address_completer.find_address("", &id, 10);
+        assert_eq!(units.len(), 3);
+        assert!(address_completer.address(&units[0].id).is_some());
+
+        let filter = AddressFilter {
+            q: Some("hovedgaden 1".to_string()),
+            ..AddressFilter::default()
+        };
+        assert_eq!(
+            address_completer.search_access_addresses(&filter).count(),
+            3
+        );
+    }
+
+    #[test]
+    fn test_search_streets() {
+        let address_completer = fixture();
+        let filter = StreetFilter {
+            zip: Some("4960".to_string()),
+            ..StreetFilter::default()
+        };
+        let streets: Vec<&str> = address_completer
+            .search_streets(&filter)
+            .map(|street| street.name.as_str())
+            .collect();
+        assert_eq!(streets, ["Maribovej"]);
+
+        let filter = StreetFilter {
+            municipal_code: Some(169),
+            ..StreetFilter::default()
+        };
+        let streets: Vec<&str> = address_completer
+            .complete_streets("h", &filter)
+            .map(|street| street.name.as_str())
+            .collect();
+        assert_eq!(streets, ["Hovedgaden"]);
+
+        let street = address_completer.street("hovedgaden").unwrap();
+        assert_eq!(street.postal_codes.len(), 3);
+    }
+
+    #[test]
+    fn test_search_postal_codes() {
+        let address_completer = fixture();
+        let filter = PostalCodeFilter {
+            municipal_code: Some(101),
+            ..PostalCodeFilter::default()
+        };
+        let postal_codes: Vec<&str> = address_completer
+            .search_postal_codes(&filter)
+            .map(|postal_code| postal_code.nr.as_str())
+            .collect();
+        assert_eq!(postal_codes, ["1165", "1218", "2500"]);
+
+        let filter = PostalCodeFilter {
+            large_recipients: true,
+            ..filter
+        };
+        assert_eq!(address_completer.search_postal_codes(&filter).count(), 4);
+        assert_eq!(
+            address_completer
+                .complete_postal_codes("københavn", &filter)
+                .count(),
+            3
+        );
+        assert_eq!(
+            address_completer.postal_code("2500").unwrap().addresses(),
+            3
+        );
+    }
+
+    #[test]
+    fn test_find_within_polygon() {
+        let address_completer = fixture();
+        let zealand = "[[[[11.0,55.0],[12.7,55.0],[12.7,56.2],[11.0,56.2],[11.0,55.0]],\
+            [[12.0,55.5],[12.3,55.5],[12.3,55.8],[12.0,55.8],[12.0,55.5]]]]";
+        let proximity = Proximity {
+            near: None,
+            within: Some(Area::Polygon(zealand.parse().unwrap())),
+        };
+        let result =
+            address_completer.find_access_address("hovedgaden".to_string(), 10, &proximity);
+        let cities: Vec<&str> = result.iter().map(|a| a.city.as_str()).collect();
+        assert_eq!(cities, ["Svinninge"]);
+
+        let result = address_completer.find_street("mari".to_string(), 10, &proximity);
+        assert_eq!(result, ["Maribovej "]);
+    }
 
     #[test]
     fn test_address_lookup() {
         let mut address_completer = AddressCompleter::new();
     }
+
+    #[test]
+    fn test_search_municipalities() {
+        let address_completer = fixture();
+        let municipality = address_completer.municipality(360).unwrap();
+        assert_eq!(municipality.name, "Lolland");
+        assert_eq!(municipality.region_code, 1085);
+
+        let filter = MunicipalityFilter {
+            region_code: Some(1084),
+            ..MunicipalityFilter::default()
+        };
+        let codes: Vec<i32> = address_completer
+            .search_municipalities(&filter)
+            .map(|municipality| municipality.code)
+            .collect();
+        assert_eq!(codes, [101, 147, 169]);
+
+        let filter = MunicipalityFilter::default();
+        let names: Vec<&str> = address_completer
+            .complete_municipalities("hø", &filter)
+            .map(|municipality| municipality.name.as_str())
+            .collect();
+        assert_eq!(names, ["Høje-Taastrup"]);
+
+        let filter = AddressFilter {
+            q: Some("maribovej lolland".to_string()),
+            ..AddressFilter::default()
+        };
+        assert_eq!(address_completer.search_addresses(&filter).count(), 4);
+        assert_eq!(
+            address_completer.search_access_addresses(&filter).count(),
+            4
+        );
+    }
+
+    #[test]
+    fn test_wash() {
+        let address_completer = fixture();
+        let washed = address_completer.wash("Maribovej 15, 1. tv, 2500 Valby");
+        assert_eq!(washed.category, washing::Category::A);
+        assert_eq!(
+            washed.candidates[0].address.id.to_string(),
+            "0a3f509f-96d9-32b8-e044-0003ba298018"
+        );
+
+        let washed = address_completer.wash("Kronprinsese Sofiesvej 1 st 2000 Frederiksberg");
+        assert_eq!(washed.category, washing::Category::B);
+        assert_eq!(washed.candidates[0].differences.street, 2);
+
+        let washed = address_completer.wash("Hovedgaden 1");
+        assert_eq!(washed.category, washing::Category::C);
+        assert_eq!(washed.candidates.len(), 3);
+
+        // Only the streets alike are compared
+        assert_eq!(address_completer.street_candidates("mariboevej").len(), 2);
+        assert!(address_completer.street_candidates("ab").is_empty());
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let address_completer = fixture();
+        let path = std::env::temp_dir().join(format!("dawa-snapshot-{}.csv", std::process::id()));
+        address_completer.save(&path).unwrap();
+
+        let mut loaded = AddressCompleter::new();
+        loaded.load(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(
+            loaded.addresses.values().collect::<Vec<_>>(),
+            address_completer.addresses.values().collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_wash_options() {
+        let mut address_completer = fixture();
+        address_completer.load_synonyms(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/fixtures/synonymer.txt"
+        ));
+        let washed = address_completer.wash("Kronprinsesse Sof. Vej 1, st, 2000 Frederiksberg");
+        assert_eq!(washed.category, washing::Category::A);
+
+        address_completer.fuzzy = false;
+        let washed = address_completer.wash("Mariboevej 10 4960 Holeby");
+        assert!(washed.candidates.is_empty());
+    }
+
+    /// xorshift64, enough to shuffle mutations reproducibly
+    struct Random(u64);
+
+    impl Random {
+        fn next(&mut self, below: usize) -> usize {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            (self.0 % below as u64) as usize
+        }
+    }
+
+    const STREETS: [(i32, i32, &str, &str, &str, &str); 4] = [
+        (360, 1013, "Maribovej", "4960", "Holeby", ""),
+        (101, 4640, "Maribovej", "2500", "Valby", ""),
+        (
+            147,
+            3384,
+            "Kronprinsesse Sofies Vej",
+            "2000",
+            "Frederiksberg",
+            "",
+        ),
+        (751, 1170, "Hovedgaden", "8210", "Aarhus V", "Brabrand"),
+    ];
+    const RENAMED: [&str; 4] = [
+        "Maribo Landevej",
+        "Maribo Allé",
+        "Kronprinsessens Vej",
+        "Hovedgade",
+    ];
+    const NUMBERS: [&str; 5] = ["1", "2", "2A", "10", "15B"];
+    const FLOORS: [&str; 4] = ["", "st", "1", "2"];
+    const DOORS: [&str; 4] = ["", "tv", "th", "mf"];
+
+    /// An address with one of 40 ids, the access address fields following
+    /// from street and house number as they do in DAWA.
+    fn random_address(random: &mut Random) -> Address {
+        let street = random.next(STREETS.len());
+        let number = random.next(NUMBERS.len());
+        let (municipal_code, street_code, name, zip, city, placename) = STREETS[street];
+        // Now and then the street has been renamed
+        let name = if random.next(8) == 0 {
+            RENAMED[street]
+        } else {
+            name
+        };
+        let large_recipient = street == 3 && number == 0;
+        Address {
+            id: Uuid::from_u128(random.next(40) as u128 + 1),
+            street_code,
+            municipal_code,
+            street: name.to_string(),
+            number: NUMBERS[number].to_string(),
+            floor: FLOORS[random.next(FLOORS.len())].to_string(),
+            door: DOORS[random.next(DOORS.len())].to_string(),
+            zip: zip.to_string(),
+            placename: placename.to_string(),
+            city: city.to_string(),
+            coordinates: Some(Point::new(
+                10.0 + street as f64 * 0.5 + number as f64 * 0.001,
+                55.0 + street as f64 * 0.2,
+            )),
+            access_address_id: Uuid::from_u128(1000 + (street * 10 + number) as u128),
+            large_recipient_zip: if large_recipient { "8100" } else { "" }.to_string(),
+            large_recipient_city: if large_recipient { "Aarhus C" } else { "" }.to_string(),
+        }
+    }
+
+    /// Every index must hold what it would when built from scratch on the
+    /// same addresses.
+    fn assert_consistent(completer: &AddressCompleter) {
+        let mut rebuilt = AddressCompleter::new();
+        for address in completer.addresses.values() {
+            rebuilt.insert_address((**address).clone()).unwrap();
+        }
+        rebuilt.build_indexes();
+
+        let access_addresses = |completer: &AddressCompleter| {
+            completer
+                .access_address
+                .iter()
+                .map(|(indicator, access_address)| {
+                    let entrances: Vec<(HouseNumber, Uuid, BTreeSet<Uuid>)> = access_address
+                        .address
+                        .iter()
+                        .map(|(number, entrance)| {
+                            let units = entrance.units.iter().map(|unit| unit.id).collect();
+                            (*number, entrance.id, units)
+                        })
+                        .collect();
+                    (*indicator, access_address.name.clone(), entrances)
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(access_addresses(completer), access_addresses(&rebuilt));
+        assert_eq!(completer.entrances, rebuilt.entrances);
+        assert_eq!(
+            completer.index.keys().collect::<Vec<_>>(),
+            rebuilt.index.keys().collect::<Vec<_>>()
+        );
+        for (name, indicator) in &completer.index {
+            assert_eq!(
+                completer.access_address[indicator].name.to_lowercase(),
+                *name
+            );
+        }
+        let trigrams = |completer: &AddressCompleter| {
+            completer
+                .trigrams
+                .iter()
+                .map(|(trigram, indicators)| {
+                    let mut indicators = indicators.clone();
+                    indicators.sort();
+                    (trigram.clone(), indicators)
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(trigrams(completer), trigrams(&rebuilt));
+        assert_eq!(
+            format!("{:?}", completer.street_names.iter().collect::<Vec<_>>()),
+            format!("{:?}", rebuilt.street_names.iter().collect::<Vec<_>>())
+        );
+        assert_eq!(
+            format!("{:?}", completer.postal_codes.iter().collect::<Vec<_>>()),
+            format!("{:?}", rebuilt.postal_codes.iter().collect::<Vec<_>>())
+        );
+        assert_eq!(
+            completer.access_addresses.keys().collect::<Vec<_>>(),
+            rebuilt.access_addresses.keys().collect::<Vec<_>>()
+        );
+        for (name, address) in &completer.access_addresses {
+            assert_eq!(completer.addresses.get(&address.id), Some(address));
+            assert_eq!(access_address_key(address), *name);
+        }
+        let everywhere = Area::BoundingBox("9,54,12,57".parse().unwrap());
+        let located = |completer: &AddressCompleter| {
+            let mut ids: Vec<Uuid> = completer
+                .spatial_index
+                .within(&everywhere)
+                .map(|address| address.access_address_id)
+                .collect();
+            ids.sort();
+            ids
+        };
+        assert_eq!(located(completer), located(&rebuilt));
+    }
+
+    #[test]
+    fn test_random_mutations() {
+        for seed in 1..=20u64 {
+            let mut random = Random(seed.wrapping_mul(0x9e37_79b9_7f4a_7c15));
+            let mut completer = AddressCompleter::new();
+            for _ in 0..200 {
+                if random.next(5) < 3 {
+                    completer
+                        .upsert_address(random_address(&mut random))
+                        .unwrap();
+                } else {
+                    completer.remove_address(&Uuid::from_u128(random.next(40) as u128 + 1));
+                }
+                assert_consistent(&completer);
+            }
+        }
+    }
+
+    #[test]
+    fn test_upsert_and_remove() {
+        let mut completer = fixture();
+        let mut address = (**completer.addresses.values().next().unwrap()).clone();
+        address.number = "1B".to_string();
+        completer.upsert_address(address.clone()).unwrap();
+        assert_eq!(completer.address(&address.id).unwrap().number, "1B");
+        assert_consistent(&completer);
+
+        // Renaming the street of one unit renames it for all
+        let id = "0a3f509f-96d7-32b8-e044-0003ba298018".parse().unwrap();
+        let mut unit = (**completer.address(&id).unwrap()).clone();
+        unit.street = "Maribo Allé".to_string();
+        completer.upsert_address(unit).unwrap();
+        assert_consistent(&completer);
+        let id = "0a3f509f-96d9-32b8-e044-0003ba298018".parse().unwrap();
+        assert_eq!(completer.address(&id).unwrap().street, "Maribo Allé");
+        assert_eq!(
+            completer.find_street("maribo a".to_string(), 1, &Proximity::default()),
+            ["Maribo Allé "]
+        );
+        let washed = completer.wash("Maribo Allé 15, 1. tv, 2500 Valby");
+        assert_eq!(washed.category, washing::Category::A);
+        assert_eq!(
+            completer.street("Maribovej").unwrap().municipal_codes.len(),
+            1
+        );
+
+        let ids: Vec<Uuid> = completer.addresses.keys().copied().collect();
+        for id in ids {
+            assert!(completer.remove_address(&id).is_some());
+            assert_consistent(&completer);
+        }
+        assert!(completer.remove_address(&address.id).is_none());
+        assert!(completer.trigrams.is_empty());
+        assert_eq!(completer.street_names.iter().count(), 0);
+        assert_eq!(completer.postal_codes.iter().count(), 0);
+    }
 }
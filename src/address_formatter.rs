@@ -0,0 +1,161 @@
+use crate::address::Address;
+use crate::address_completer::AccessAddressRef;
+
+/// Writes addresses the way DAWA writes `betegnelse`, e.g.
+/// `Maribovej 15, st. tv, 2500 Valby` or, over several lines as on an
+/// envelope, with the supplerende bynavn on a line of its own.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct AddressFormatter {
+    /// One line per part instead of a single line separated by commas
+    pub multiline: bool,
+    /// Leaves out floor and door, as for an adgangsadresse
+    pub access_address: bool,
+    /// Uses the stormodtager postal code when the address has one
+    pub large_recipient: bool,
+}
+
+impl AddressFormatter {
+    pub fn format(&self, address: &Address) -> String {
+        let mut street = format!("{} {}", address.street, address.number);
+        if !self.access_address {
+            let floor = address.floor.trim_end_matches('.');
+            if !floor.is_empty() || !address.door.is_empty() {
+                street.push(',');
+            }
+            if !floor.is_empty() {
+                street.push_str(&format!(" {}.", floor));
+            }
+            if !address.door.is_empty() {
+                street.push_str(&format!(" {}", address.door));
+            }
+        }
+        self.lines(
+            street,
+            &address.placename,
+            (&address.zip, &address.city),
+            (&address.large_recipient_zip, &address.large_recipient_city),
+        )
+    }
+
+    pub fn format_access_address(&self, access_address: &AccessAddressRef) -> String {
+        self.lines(
+            format!("{} {}", access_address.street, access_address.number),
+            access_address.placename,
+            (access_address.zip, access_address.city),
+            (
+                access_address.large_recipient_zip,
+                access_address.large_recipient_city,
+            ),
+        )
+    }
+
+    fn lines(
+        &self,
+        street: String,
+        placename: &str,
+        postal_code: (&str, &str),
+        large_recipient: (&str, &str),
+    ) -> String {
+        let (zip, city) = if self.large_recipient && !large_recipient.0.is_empty() {
+            large_recipient
+        } else {
+            postal_code
+        };
+        let mut lines = vec![street];
+        if !placename.is_empty() {
+            lines.push(placename.to_string());
+        }
+        lines.push(format!("{} {}", zip, city));
+        lines.join(if self.multiline { "\n" } else { ", " })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::address_completer::fixture;
+
+    fn address(floor: &str, door: &str, placename: &str) -> Address {
+        Address {
+            street: "Nørregade".to_string(),
+            number: "12A".to_string(),
+            floor: floor.to_string(),
+            door: door.to_string(),
+            placename: placename.to_string(),
+            zip: "1165".to_string(),
+            city: "København K".to_string(),
+            ..Address::default()
+        }
+    }
+
+    #[test]
+    fn test_golden() {
+        let completer = fixture();
+        let mut reader = csv::Reader::from_path(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/fixtures/betegnelser.csv"
+        ))
+        .unwrap();
+        let mut count = 0;
+        for record in reader.records() {
+            let record = record.unwrap();
+            let address = completer.address(&record[0].parse().unwrap()).unwrap();
+            assert_eq!(address.display_name(), &record[1]);
+            let access_address = completer
+                .access_address(&address.access_address_id)
+                .unwrap();
+            assert_eq!(access_address.display_name(), &record[2]);
+            count += 1;
+        }
+        assert_eq!(count, completer.address_count());
+    }
+
+    #[test]
+    fn test_units() {
+        let formatter = AddressFormatter::default();
+        let cases = [
+            (("", "", ""), "Nørregade 12A, 1165 København K"),
+            (("st", "tv", ""), "Nørregade 12A, st. tv, 1165 København K"),
+            (("kl", "", ""), "Nørregade 12A, kl., 1165 København K"),
+            (("k2", "3", ""), "Nørregade 12A, k2. 3, 1165 København K"),
+            (("st.", "", ""), "Nørregade 12A, st., 1165 København K"),
+            (("", "12", ""), "Nørregade 12A, 12, 1165 København K"),
+            (
+                ("2", "mf", "Indre By"),
+                "Nørregade 12A, 2. mf, Indre By, 1165 København K",
+            ),
+        ];
+        for ((floor, door, placename), expected) in cases {
+            assert_eq!(formatter.format(&address(floor, door, placename)), expected);
+        }
+    }
+
+    #[test]
+    fn test_multiline_and_large_recipient() {
+        let formatter = AddressFormatter {
+            multiline: true,
+            ..AddressFormatter::default()
+        };
+        assert_eq!(
+            formatter.format(&address("1", "th", "Indre By")),
+            "Nørregade 12A, 1. th\nIndre By\n1165 København K"
+        );
+
+        let mut address = address("", "", "");
+        address.large_recipient_zip = "1092".to_string();
+        address.large_recipient_city = "København K".to_string();
+        let formatter = AddressFormatter {
+            large_recipient: true,
+            access_address: true,
+            ..AddressFormatter::default()
+        };
+        assert_eq!(
+            formatter.format(&address),
+            "Nørregade 12A, 1092 København K"
+        );
+        assert_eq!(
+            AddressFormatter::default().format(&address),
+            "Nørregade 12A, 1165 København K"
+        );
+    }
+}
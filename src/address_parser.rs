@@ -0,0 +1,250 @@
+use crate::unit::{Door, Floor};
+
+/// The parts of a free text address such as `Maribovej 15, 1. tv, 2500 Valby`.
+/// Parts not found in the text are `None`.
+#[derive(Debug, Default, PartialEq, Eq, Clone)]
+pub struct ParsedAddress {
+    pub street: Option<String>,
+    pub number: Option<String>,
+    pub floor: Option<Floor>,
+    pub door: Option<Door>,
+    pub placename: Option<String>,
+    pub zip: Option<String>,
+    pub city: Option<String>,
+}
+
+#[derive(Debug, PartialEq)]
+enum Token<'a> {
+    Word(&'a str),
+    Comma,
+}
+
+fn tokenize(text: &str) -> Vec<Token<'_>> {
+    let mut tokens = Vec::new();
+    for word in text.split_whitespace() {
+        for (i, part) in word.split(',').enumerate() {
+            if i > 0 {
+                tokens.push(Token::Comma);
+            }
+            if !part.is_empty() {
+                tokens.push(Token::Word(part));
+            }
+        }
+    }
+    tokens
+}
+
+fn is_zip(word: &str) -> bool {
+    word.len() == 4 && word.chars().all(|c| c.is_ascii_digit())
+}
+
+fn is_house_number(word: &str) -> bool {
+    let digits = word.trim_end_matches(|c: char| c.is_alphabetic());
+    !digits.is_empty()
+        && digits.len() <= 3
+        && digits.chars().all(|c| c.is_ascii_digit())
+        && word.len() - digits.len() <= 1
+}
+
+/// Floor and door from words such as `1. sal tv`, `st th` or `kl`, `None`
+/// when the words are something else, e.g. a supplerende bynavn.
+fn floor_and_door(words: &[&str]) -> Option<(Option<Floor>, Option<Door>)> {
+    let words: Vec<&str> = words
+        .iter()
+        .copied()
+        .filter(|word| !matches!(word.to_lowercase().as_str(), "sal" | "sal." | "til"))
+        .collect();
+    match words.as_slice() {
+        [] => None,
+        [word] => match word.parse() {
+            Ok(floor) => Some((Some(floor), None)),
+            Err(_) if is_door_word(word) => Some((None, word.parse().ok())),
+            Err(_) => None,
+        },
+        [floor, door] => Some((Some(floor.parse().ok()?), Some(door.parse().ok()?))),
+        _ => None,
+    }
+}
+
+/// Doors that can't be mistaken for a place name when written on their own.
+fn is_door_word(word: &str) -> bool {
+    matches!(
+        word.parse(),
+        Ok(Door::Left) | Ok(Door::Middle) | Ok(Door::Right)
+    )
+}
+
+fn join(words: &[&str]) -> Option<String> {
+    Some(words.join(" ")).filter(|text| !text.is_empty())
+}
+
+/// Splits a free text address into its parts. The street name runs up to the
+/// house number, postal code and city are found from the end, and what is in
+/// between is floor, door and supplerende bynavn.
+pub fn parse(text: &str) -> ParsedAddress {
+    let tokens = tokenize(text);
+    let words: Vec<&str> = tokens
+        .iter()
+        .filter_map(|token| match token {
+            Token::Word(word) => Some(*word),
+            Token::Comma => None,
+        })
+        .collect();
+
+    let mut parsed = ParsedAddress::default();
+    let Some(number_index) = words
+        .iter()
+        .skip(1)
+        .position(|word| is_house_number(word))
+        .map(|index| index + 1)
+    else {
+        parsed.street = join(&words);
+        return parsed;
+    };
+    parsed.street = join(&words[..number_index]);
+    parsed.number = Some(words[number_index].to_uppercase());
+
+    let zip_index = words
+        .iter()
+        .rposition(|word| is_zip(word))
+        .filter(|index| *index > number_index);
+    let end = zip_index.unwrap_or(words.len());
+    if let Some(zip_index) = zip_index {
+        parsed.zip = Some(words[zip_index].to_string());
+        parsed.city = join(&words[zip_index + 1..]);
+    }
+
+    // The words between house number and postal code, grouped by the commas
+    // in the text.
+    let mut groups: Vec<Vec<&str>> = vec![Vec::new()];
+    let mut word_index = 0;
+    for token in &tokens {
+        match token {
+            Token::Word(word) => {
+                if word_index > number_index && word_index < end {
+                    groups.last_mut().unwrap().push(word);
+                }
+                word_index += 1;
+            }
+            Token::Comma => {
+                if word_index > number_index && word_index < end {
+                    groups.push(Vec::new());
+                }
+            }
+        }
+    }
+    let mut groups = groups.into_iter().filter(|group| !group.is_empty());
+    let mut placename: Vec<&str> = Vec::new();
+
+    // A house number letter written apart, `15 A`
+    let mut first = groups.next().unwrap_or_default();
+    if let Some(letter) = first.first().filter(|word| {
+        word.len() == 1
+            && word.chars().all(|c| c.is_alphabetic())
+            && parsed
+                .number
+                .as_ref()
+                .unwrap()
+                .chars()
+                .all(|c| c.is_ascii_digit())
+    }) {
+        parsed
+            .number
+            .as_mut()
+            .unwrap()
+            .push_str(&letter.to_uppercase());
+        first.remove(0);
+    }
+
+    for group in std::iter::once(first).chain(groups) {
+        if group.is_empty() {
+            continue;
+        }
+        match floor_and_door(&group) {
+            Some((floor, door)) if parsed.floor.is_none() && parsed.door.is_none() => {
+                parsed.floor = floor;
+                parsed.door = door;
+            }
+            _ => placename.extend(group),
+        }
+    }
+    // Without a postal code the trailing words are taken to be the city
+    if parsed.zip.is_none() {
+        parsed.city = join(&placename);
+    } else {
+        parsed.placename = join(&placename);
+    }
+    parsed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn some(value: &str) -> Option<String> {
+        Some(value.to_string())
+    }
+
+    #[test]
+    fn test_parse_full_address() {
+        assert_eq!(
+            parse("Maribovej 15, 1. tv, 2500 Valby"),
+            ParsedAddress {
+                street: some("Maribovej"),
+                number: some("15"),
+                floor: Some(Floor::new(1)),
+                door: Some(Door::Left),
+                placename: None,
+                zip: some("2500"),
+                city: some("Valby"),
+            }
+        );
+        assert_eq!(
+            parse("maribovej 15 1 sal th 2500 valby"),
+            ParsedAddress {
+                street: some("maribovej"),
+                number: some("15"),
+                floor: Some(Floor::new(1)),
+                door: Some(Door::Right),
+                placename: None,
+                zip: some("2500"),
+                city: some("valby"),
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_placename_and_letters() {
+        let parsed = parse("Hovedgaden 1, Brabrand, 8210 Aarhus V");
+        assert_eq!(parsed.street, some("Hovedgaden"));
+        assert_eq!(parsed.placename, some("Brabrand"));
+        assert_eq!(parsed.city, some("Aarhus V"));
+        assert_eq!(parsed.floor, None);
+
+        let parsed = parse("Kronprinsesse Sofies Vej 1 b, st., 2000 Frederiksberg");
+        assert_eq!(parsed.street, some("Kronprinsesse Sofies Vej"));
+        assert_eq!(parsed.number, some("1B"));
+        assert_eq!(parsed.floor, Some(Floor::GROUND));
+        assert_eq!(parsed.door, None);
+    }
+
+    #[test]
+    fn test_parse_partial_address() {
+        let parsed = parse("Maribovej");
+        assert_eq!(parsed.street, some("Maribovej"));
+        assert_eq!(parsed.number, None);
+
+        let parsed = parse("Maribovej 10 Holeby");
+        assert_eq!(parsed.number, some("10"));
+        assert_eq!(parsed.zip, None);
+        assert_eq!(parsed.city, some("Holeby"));
+
+        let parsed = parse("Maribovej 15 st. t.v.");
+        assert_eq!(parsed.floor, Some(Floor::GROUND));
+        assert_eq!(parsed.door, Some(Door::Left));
+
+        let parsed = parse("Maribovej 15 kælder 3");
+        assert_eq!(parsed.floor, Some(Floor::BASEMENT));
+        assert_eq!(parsed.door, Some(Door::Number(3)));
+    }
+}
@@ -1 +1,114 @@
+use std::sync::Arc;
+
+use uuid::Uuid;
+
+use crate::address::Address;
+use crate::address_completer::{AccessAddressRef, AddressCompleter, Proximity};
+use crate::street_index::Street;
+
 pub mod r#static;
+
+/// What `/autocomplete` suggests from, chosen with `search.backend`.
+pub trait SearchBackend: Send + Sync {
+    /// Street names starting with the text, each followed by a space.
+    fn complete_streets(&self, q: &str, count: usize, proximity: &Proximity) -> Vec<String>;
+
+    /// An address of each access address starting with the text.
+    fn complete_access_addresses(
+        &self,
+        q: &str,
+        count: usize,
+        proximity: &Proximity,
+    ) -> Vec<Arc<Address>>;
+
+    /// The units of an access address, narrowed down by a floor or door in
+    /// the text.
+    fn complete_units(&self, q: &str, access_address_id: &Uuid, count: usize) -> Vec<Arc<Address>>;
+
+    fn address(&self, id: &Uuid) -> Option<Arc<Address>>;
+
+    fn access_address(&self, id: &Uuid) -> Option<AccessAddressRef<'_>>;
+
+    /// A street by name, whatever the case.
+    fn street(&self, name: &str) -> Option<&Street>;
+
+    /// The name of a kommune, empty when unknown.
+    fn municipality_name(&self, code: i32) -> &str;
+}
+
+fn limit(count: usize) -> i32 {
+    i32::try_from(count).unwrap_or(i32::MAX)
+}
+
+impl SearchBackend for AddressCompleter {
+    fn complete_streets(&self, q: &str, count: usize, proximity: &Proximity) -> Vec<String> {
+        self.find_street(q.to_string(), limit(count), proximity)
+    }
+
+    fn complete_access_addresses(
+        &self,
+        q: &str,
+        count: usize,
+        proximity: &Proximity,
+    ) -> Vec<Arc<Address>> {
+        self.find_access_address(q.to_string(), limit(count), proximity)
+    }
+
+    fn complete_units(&self, q: &str, access_address_id: &Uuid, count: usize) -> Vec<Arc<Address>> {
+        self.find_address(q, access_address_id, limit(count))
+    }
+
+    fn address(&self, id: &Uuid) -> Option<Arc<Address>> {
+        AddressCompleter::address(self, id).cloned()
+    }
+
+    fn access_address(&self, id: &Uuid) -> Option<AccessAddressRef<'_>> {
+        AddressCompleter::access_address(self, id)
+    }
+
+    fn street(&self, name: &str) -> Option<&Street> {
+        AddressCompleter::street(self, name)
+    }
+
+    fn municipality_name(&self, code: i32) -> &str {
+        AddressCompleter::municipality_name(self, code)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::r#static::StaticBackend;
+    use super::*;
+    use crate::address_completer::fixture;
+
+    fn backends() -> Vec<Box<dyn SearchBackend>> {
+        vec![Box::new(fixture()), Box::new(StaticBackend::new())]
+    }
+
+    #[test]
+    fn test_backends() {
+        let proximity = Proximity::default();
+        for backend in backends() {
+            let streets = backend.complete_streets("maribo", 1, &proximity);
+            assert_eq!(streets.len(), 1);
+            assert!(streets.iter().all(|street| street.ends_with(' ')));
+
+            let access_addresses = backend.complete_access_addresses("maribovej 1", 1, &proximity);
+            assert_eq!(access_addresses.len(), 1);
+            let access_address_id = access_addresses[0].access_address_id;
+            assert!(backend.access_address(&access_address_id).is_some());
+
+            let id = "0a3f507a-c086-32b8-e044-0003ba298018".parse().unwrap();
+            let units = backend.complete_units("Maribovej 15, st", &id, 10);
+            assert!(!units.is_empty());
+            assert!(units.iter().all(|unit| unit.access_address_id == id));
+            let unit = backend.address(&units[0].id).unwrap();
+            assert_eq!(unit.display_name(), "Maribovej 15, st. tv, 2500 Valby");
+
+            let street = backend.street("maribovej").unwrap();
+            assert_eq!(street.name, "Maribovej");
+            assert!(street.postal_codes.contains_key("2500"));
+            assert_eq!(backend.municipality_name(101), "København");
+        }
+    }
+}
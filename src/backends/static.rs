@@ -1,44 +1,148 @@
-use serde_json::{json, Value};
-
-pub fn autocomplete(
-    q: Option<String>,
-    r#type: Option<String>,
-    fuzzy: Option<String>,
-    caretpos: Option<String>,
-    per_side: Option<String>,
-) -> Value {
-    json!(
-        [
-            {
-              "type": "vejnavn",
-              "tekst": "Maribo Landevej ",
-              "forslagstekst": "Maribo Landevej",
-              "caretpos": 16,
-              "data": {
-                "navn": "Maribo Landevej",
-                "href": "https://api.dataforsyningen.dk/vejnavne/Maribo%20Landevej"
-              }
-            },
-            {
-              "type": "vejnavn",
-              "tekst": "Maribovej ",
-              "forslagstekst": "Maribovej",
-              "caretpos": 10,
-              "data": {
-                "navn": "Maribovej",
-                "href": "https://api.dataforsyningen.dk/vejnavne/Maribovej"
-              }
-            },
+use std::sync::Arc;
+
+use uuid::{uuid, Uuid};
+
+use super::SearchBackend;
+use crate::address::Address;
+use crate::address_completer::{AccessAddressRef, Proximity};
+use crate::geo::Point;
+use crate::street_index::{Street, StreetIndex};
+
+const ACCESS_ADDRESS_ID: Uuid = uuid!("0a3f507a-c086-32b8-e044-0003ba298018");
+
+/// The same few Maribovej suggestions whatever the text, for trying out
+/// clients without loading any data.
+pub struct StaticBackend {
+    /// Maribovej 1 in Holeby, then the units of Maribovej 15 in Valby
+    addresses: Vec<Arc<Address>>,
+    streets: StreetIndex,
+}
+
+impl StaticBackend {
+    pub fn new() -> Self {
+        let valby = |id: Uuid, floor: &str, door: &str| Address {
+            id,
+            street_code: 4640,
+            municipal_code: 101,
+            street: "Maribovej".to_string(),
+            number: "15".to_string(),
+            floor: floor.to_string(),
+            door: door.to_string(),
+            zip: "2500".to_string(),
+            city: "Valby".to_string(),
+            coordinates: Some(Point::new(12.48971377, 55.667307)),
+            access_address_id: ACCESS_ADDRESS_ID,
+            ..Address::default()
+        };
+        let addresses = vec![
+            Arc::new(Address {
+                id: uuid!("58910400-b8f1-44bf-8293-7420ee1595a8"),
+                street_code: 1013,
+                municipal_code: 360,
+                street: "Maribovej".to_string(),
+                number: "1".to_string(),
+                zip: "4960".to_string(),
+                city: "Holeby".to_string(),
+                coordinates: Some(Point::new(11.45702023, 54.71182365)),
+                access_address_id: uuid!("0a3f507a-b2e6-32b8-e044-0003ba298018"),
+                ..Address::default()
+            }),
+            Arc::new(valby(
+                uuid!("0a3f509f-96d7-32b8-e044-0003ba298018"),
+                "st",
+                "tv",
+            )),
+            Arc::new(valby(
+                uuid!("0a3f509f-96d8-32b8-e044-0003ba298018"),
+                "st",
+                "th",
+            )),
+            Arc::new(valby(
+                uuid!("0a3f509f-96d9-32b8-e044-0003ba298018"),
+                "1",
+                "tv",
+            )),
+        ];
+        let mut streets = StreetIndex::new();
+        for address in &addresses {
+            streets.insert(address);
+        }
+        StaticBackend { addresses, streets }
+    }
+}
+
+impl Default for StaticBackend {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SearchBackend for StaticBackend {
+    fn complete_streets(&self, _q: &str, count: usize, _proximity: &Proximity) -> Vec<String> {
+        self.streets
+            .iter()
+            .take(count)
+            .map(|street| format!("{} ", street.name))
+            .collect()
+    }
+
+    fn complete_access_addresses(
+        &self,
+        _q: &str,
+        count: usize,
+        _proximity: &Proximity,
+    ) -> Vec<Arc<Address>> {
+        // One address per access address
+        let mut result: Vec<Arc<Address>> = Vec::new();
+        for address in &self.addresses {
+            if !result
+                .iter()
+                .any(|other| other.access_address_id == address.access_address_id)
             {
-              "type": "vejnavn",
-              "tekst": "Gl. Maribovej ",
-              "forslagstekst": "Gl. Maribovej",
-              "caretpos": 14,
-              "data": {
-                "navn": "Gl. Maribovej",
-                "href": "https://api.dataforsyningen.dk/vejnavne/Gl.%20Maribovej"
-              }
+                result.push(address.clone());
             }
-          ]
-    )
+        }
+        result.truncate(count);
+        result
+    }
+
+    fn complete_units(
+        &self,
+        _q: &str,
+        access_address_id: &Uuid,
+        count: usize,
+    ) -> Vec<Arc<Address>> {
+        self.addresses
+            .iter()
+            .filter(|address| address.access_address_id == *access_address_id)
+            .take(count)
+            .cloned()
+            .collect()
+    }
+
+    fn address(&self, id: &Uuid) -> Option<Arc<Address>> {
+        self.addresses
+            .iter()
+            .find(|address| address.id == *id)
+            .cloned()
+    }
+
+    fn access_address(&self, id: &Uuid) -> Option<AccessAddressRef<'_>> {
+        self.addresses
+            .iter()
+            .find(|address| address.access_address_id == *id)
+            .map(|address| AccessAddressRef::from(&**address))
+    }
+
+    fn street(&self, name: &str) -> Option<&Street> {
+        self.streets.get(name)
+    }
+
+    fn municipality_name(&self, code: i32) -> &str {
+        match code {
+            101 => "København",
+            360 => "Lolland",
+            _ => "",
+        }
+    }
 }
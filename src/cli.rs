@@ -0,0 +1,140 @@
+use std::{net::IpAddr, path::PathBuf};
+
+use clap::{Args, Parser, Subcommand};
+use rocket::figment::Figment;
+
+/// A DAWA compatible address completion API for Denmark. Without a command
+/// the API server is started.
+#[derive(Debug, Parser)]
+#[command(version, about)]
+pub struct Cli {
+    /// TOML file with settings, in the same format as Rocket.toml
+    #[arg(long, global = true)]
+    pub config: Option<PathBuf>,
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Start the API server
+    Serve(ServeArgs),
+    /// Convert a DAWA address export (`/adresser?format=csv`) to an address
+    /// file
+    Convert(ConvertArgs),
+    /// Write the loaded addresses to a single address file
+    Snapshot(SnapshotArgs),
+    /// Print the autocomplete suggestions for a text, as `/autocomplete`
+    /// returns them
+    Query(QueryArgs),
+    /// Match the addresses in a CSV file, appending id, address, coordinates
+    /// and confidence to every row
+    Match(MatchArgs),
+    /// Apply the changes DAWA has published since the last update to the
+    /// address file
+    Update(UpdateArgs),
+}
+
+#[derive(Debug, Default, Args)]
+pub struct DataArgs {
+    /// Directory with address.csv and kommuner.csv, overriding `data.dir`
+    #[arg(long)]
+    pub data: Option<PathBuf>,
+}
+
+impl DataArgs {
+    pub fn apply(&self, figment: Figment) -> Figment {
+        match &self.data {
+            Some(data) => figment.merge(("data.dir", data)),
+            None => figment,
+        }
+    }
+}
+
+#[derive(Debug, Default, Args)]
+pub struct ServeArgs {
+    #[command(flatten)]
+    pub data: DataArgs,
+    /// Port to listen on, overriding Rocket's configuration
+    #[arg(long)]
+    pub port: Option<u16>,
+    /// Address to listen on, overriding Rocket's configuration
+    #[arg(long)]
+    pub bind: Option<IpAddr>,
+}
+
+#[derive(Debug, Args)]
+pub struct ConvertArgs {
+    /// DAWA address export
+    #[arg(long)]
+    pub input: PathBuf,
+    /// Address file to write
+    #[arg(long)]
+    pub output: PathBuf,
+}
+
+#[derive(Debug, Args)]
+pub struct SnapshotArgs {
+    #[command(flatten)]
+    pub data: DataArgs,
+    /// Address file to write
+    #[arg(long)]
+    pub output: PathBuf,
+}
+
+#[derive(Debug, Args)]
+pub struct QueryArgs {
+    #[command(flatten)]
+    pub data: DataArgs,
+    /// The text typed so far
+    pub text: String,
+    /// Suggest adgangsadresse or adresse rather than streets
+    #[arg(long)]
+    pub startfra: Option<String>,
+    /// Start from this access address id, picking units
+    #[arg(long)]
+    pub adgangsadresseid: Option<String>,
+    /// How many suggestions to print
+    #[arg(long)]
+    pub per_side: Option<usize>,
+}
+
+#[derive(Debug, Args)]
+pub struct MatchArgs {
+    #[command(flatten)]
+    pub data: DataArgs,
+    /// CSV file with a header row
+    #[arg(long)]
+    pub input: PathBuf,
+    /// The column holding the address text
+    #[arg(long)]
+    pub column: String,
+    /// Where matched rows are written
+    #[arg(long)]
+    pub output: PathBuf,
+    /// Where rows without a certain match are written, next to the output
+    /// file as `<output>.rejects.csv` when left out
+    #[arg(long)]
+    pub rejects: Option<PathBuf>,
+}
+
+#[derive(Debug, Args)]
+pub struct UpdateArgs {
+    #[command(flatten)]
+    pub data: DataArgs,
+    /// Replication URL or directory with events, overriding `replication.source`
+    #[arg(long)]
+    pub source: Option<String>,
+    /// Sequence number the address file is up to date with, overriding the
+    /// sequence file
+    #[arg(long)]
+    pub from: Option<u64>,
+}
+
+impl MatchArgs {
+    pub fn rejects(&self) -> PathBuf {
+        self.rejects
+            .clone()
+            .unwrap_or_else(|| self.output.with_extension("rejects.csv"))
+    }
+}
@@ -0,0 +1,334 @@
+use std::path::{Path, PathBuf};
+
+use rocket::{
+    figment::{
+        providers::{Env, Format, Toml},
+        Figment,
+    },
+    serde::{Deserialize, Serialize},
+};
+
+/// Everything configurable besides Rocket's own settings. Read from the same
+/// figment as Rocket, so it can be set in `Rocket.toml`, with `ROCKET_`
+/// variables, in the file given with `--config`, or with `DAWA_` variables
+/// using `__` between levels, e.g. `DAWA_SEARCH__PER_SIDE=20`.
+#[derive(Debug, Default, Clone, Deserialize, Serialize)]
+#[serde(crate = "rocket::serde", default)]
+pub struct Settings {
+    pub data: DataSettings,
+    pub index: IndexSettings,
+    pub search: SearchSettings,
+    pub cors: CorsSettings,
+    pub reload: ReloadSettings,
+    pub replication: ReplicationSettings,
+    /// Where `href` links point, the address and port this server is bound to
+    /// when not set
+    pub href_base: Option<String>,
+    pub logging: LoggingSettings,
+}
+
+/// The data files, relative to `dir`.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(crate = "rocket::serde", default)]
+pub struct DataSettings {
+    pub dir: PathBuf,
+    pub addresses: PathBuf,
+    pub municipalities: PathBuf,
+    /// DAWA address export converted when `addresses` does not exist
+    pub dawa_export: PathBuf,
+}
+
+impl Default for DataSettings {
+    fn default() -> Self {
+        DataSettings {
+            dir: PathBuf::from("."),
+            addresses: PathBuf::from("address.csv"),
+            municipalities: PathBuf::from("kommuner.csv"),
+            dawa_export: PathBuf::from("../addresser.csv"),
+        }
+    }
+}
+
+impl DataSettings {
+    pub fn addresses(&self) -> PathBuf {
+        self.dir.join(&self.addresses)
+    }
+
+    pub fn municipalities(&self) -> PathBuf {
+        self.dir.join(&self.municipalities)
+    }
+
+    pub fn dawa_export(&self) -> PathBuf {
+        self.dir.join(&self.dawa_export)
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(crate = "rocket::serde", default)]
+pub struct IndexSettings {
+    /// Whether washing considers misspelled street names
+    pub fuzzy: bool,
+    /// File with a `word = replacement` per line, e.g. `gl. = gammel`
+    pub synonyms: Option<PathBuf>,
+}
+
+impl Default for IndexSettings {
+    fn default() -> Self {
+        IndexSettings {
+            fuzzy: true,
+            synonyms: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(crate = "rocket::serde", default)]
+pub struct SearchSettings {
+    /// Results per page when `per_side` is not given
+    pub per_side: usize,
+    /// The largest `per_side` accepted
+    pub max_per_side: usize,
+    /// Whether search results carry `X-Total-Count` and `Link` headers, which
+    /// takes finding every result rather than just the page
+    pub total_count: bool,
+    /// What `/autocomplete` suggests from
+    pub backend: BackendKind,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(crate = "rocket::serde", rename_all = "lowercase")]
+pub enum BackendKind {
+    /// The addresses loaded from `data`
+    #[default]
+    Memory,
+    /// The same few Maribovej addresses whatever the text, for trying out a
+    /// client without any data
+    Static,
+}
+
+impl Default for SearchSettings {
+    fn default() -> Self {
+        SearchSettings {
+            per_side: 50,
+            max_per_side: 1000,
+            total_count: false,
+            backend: BackendKind::Memory,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(crate = "rocket::serde", default)]
+pub struct CorsSettings {
+    /// Origins allowed to call the API: `*` for any, an origin such as
+    /// `https://example.dk`, or a pattern such as `https://*.example.dk`
+    pub origins: Vec<String>,
+    /// Whether browsers may send cookies, not allowed with `*`
+    pub credentials: bool,
+    /// Seconds browsers may cache a preflight response
+    pub max_age: u64,
+}
+
+impl Default for CorsSettings {
+    fn default() -> Self {
+        CorsSettings {
+            origins: vec!["*".to_string()],
+            credentials: false,
+            max_age: 86400,
+        }
+    }
+}
+
+/// When to rebuild the indexes without restarting. `SIGHUP` always reloads.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(crate = "rocket::serde", default)]
+pub struct ReloadSettings {
+    /// Whether to reload when the address file or the DAWA export changes
+    pub watch: bool,
+    /// Seconds between checking the files for changes
+    pub interval: u64,
+    /// Bearer token for `POST /admin/reload`, which is refused when not set
+    pub token: Option<String>,
+}
+
+impl Default for ReloadSettings {
+    fn default() -> Self {
+        ReloadSettings {
+            watch: false,
+            interval: 10,
+            token: None,
+        }
+    }
+}
+
+/// Where the `update` command gets DAWA's address changes from.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(crate = "rocket::serde", default)]
+pub struct ReplicationSettings {
+    /// The `replikering/haendelser` URL, or a directory with a saved response
+    /// per entity
+    pub source: Option<String>,
+    /// Holds the last sequence number applied, relative to `data.dir`
+    pub sequence: PathBuf,
+    /// Holds the access addresses without units yet and the streets and
+    /// postal codes without addresses, relative to `data.dir`
+    pub pending: PathBuf,
+}
+
+impl Default for ReplicationSettings {
+    fn default() -> Self {
+        ReplicationSettings {
+            source: None,
+            sequence: PathBuf::from("sekvensnummer.txt"),
+            pending: PathBuf::from("replikering.json"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(crate = "rocket::serde", default)]
+pub struct LoggingSettings {
+    /// An `env_logger` filter such as `info` or `warn,dawa_autocomplete=debug`,
+    /// `RUST_LOG` takes precedence
+    pub filter: String,
+}
+
+impl Default for LoggingSettings {
+    fn default() -> Self {
+        LoggingSettings {
+            filter: "info".to_string(),
+        }
+    }
+}
+
+/// Rocket's figment with the `--config` file and `DAWA_` variables merged in.
+pub fn figment(config: Option<&Path>) -> Figment {
+    let mut figment = rocket::Config::figment();
+    if let Some(config) = config {
+        figment = figment.merge(Toml::file(config).nested());
+    }
+    figment.merge(Env::prefixed("DAWA_").split("__").global())
+}
+
+impl Settings {
+    /// Checks the settings, listing every problem found.
+    pub fn validate(&self) -> Result<(), String> {
+        let mut errors = Vec::new();
+        if self.search.backend == BackendKind::Static {
+            // No data is loaded
+        } else if !self.data.dir.is_dir() {
+            errors.push(format!(
+                "data.dir: {} is not a directory",
+                self.data.dir.display()
+            ));
+        } else if !self.data.addresses().exists() && !self.data.dawa_export().exists() {
+            errors.push(format!(
+                "data.addresses: neither {} nor {} exists, use curl https://api.dataforsyningen.dk/adresser?format=csv > {}",
+                self.data.addresses().display(),
+                self.data.dawa_export().display(),
+                self.data.dawa_export().display()
+            ));
+        }
+        if let Some(synonyms) = &self.index.synonyms {
+            if !synonyms.is_file() {
+                errors.push(format!("index.synonyms: {} not found", synonyms.display()));
+            }
+        }
+        if self.search.per_side == 0 {
+            errors.push("search.per_side: must be at least 1".to_string());
+        } else if self.search.per_side > self.search.max_per_side {
+            errors.push(format!(
+                "search.per_side: must be at most search.max_per_side, {}",
+                self.search.max_per_side
+            ));
+        }
+        for origin in &self.cors.origins {
+            if origin != "*" && (!is_http_url(origin) || origin.matches('*').count() > 1) {
+                errors.push(format!(
+                    "cors.origins: {} is neither * nor an http(s) origin",
+                    origin
+                ));
+            }
+        }
+        if self.cors.credentials && self.cors.origins.iter().any(|origin| origin == "*") {
+            errors.push("cors.credentials: can't be used with the origin *".to_string());
+        }
+        if self.reload.interval == 0 {
+            errors.push("reload.interval: must be at least 1".to_string());
+        }
+        if let Some(href_base) = &self.href_base {
+            if !is_http_url(href_base) {
+                errors.push(format!("href_base: {} is not an http(s) URL", href_base));
+            }
+        }
+        if self.logging.filter.trim().is_empty() {
+            errors.push("logging.filter: must not be empty".to_string());
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(format!("Invalid configuration:\n  {}", errors.join("\n  ")))
+        }
+    }
+}
+
+fn is_http_url(url: &str) -> bool {
+    ["http://", "https://"].iter().any(|scheme| {
+        url.strip_prefix(scheme)
+            .is_some_and(|rest| !rest.is_empty() && !rest.starts_with('/'))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_defaults_and_overrides() {
+        let settings: Settings = Figment::new().extract().unwrap();
+        assert_eq!(settings.search.per_side, 50);
+        assert_eq!(settings.cors.origins, ["*"]);
+        assert_eq!(
+            settings.data.addresses(),
+            Path::new(".").join("address.csv")
+        );
+
+        let settings: Settings = Figment::new()
+            .merge(Toml::string(
+                r#"
+                href_base = "https://adresser.example.dk"
+                [search]
+                per_side = 20
+                backend = "static"
+                [index]
+                fuzzy = false
+                "#,
+            ))
+            .merge(("data.dir", "fixtures"))
+            .extract()
+            .unwrap();
+        assert_eq!(settings.search.per_side, 20);
+        assert_eq!(settings.search.backend, BackendKind::Static);
+        assert!(!settings.index.fuzzy);
+        assert_eq!(settings.data.dir, Path::new("fixtures"));
+    }
+
+    #[test]
+    fn test_validate() {
+        let mut settings = Settings::default();
+        settings.data.dir = PathBuf::from(concat!(env!("CARGO_MANIFEST_DIR"), "/fixtures"));
+        assert_eq!(settings.validate(), Ok(()));
+
+        settings.search.per_side = 0;
+        settings.cors.origins = vec!["example.dk".to_string()];
+        settings.href_base = Some("https://adresser.example.dk".to_string());
+        let error = settings.validate().unwrap_err();
+        assert_eq!(
+            error,
+            "Invalid configuration:\n  \
+             search.per_side: must be at least 1\n  \
+             cors.origins: example.dk is neither * nor an http(s) origin"
+        );
+    }
+}
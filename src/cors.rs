@@ -0,0 +1,227 @@
+use rocket::{
+    fairing::{self, Fairing, Info, Kind},
+    http::{Header, Method, Status},
+    Build, Request, Response, Rocket,
+};
+
+use crate::config::CorsSettings;
+
+/// Methods the API answers cross-origin requests for
+const ALLOWED_METHODS: &str = "GET, POST, OPTIONS";
+
+/// Adds CORS headers for the configured origins and answers `OPTIONS`
+/// preflight requests on every path.
+pub struct Cors(pub CorsSettings);
+
+impl Cors {
+    /// Whether any origin gets `*`, so responses don't depend on `Origin`.
+    fn allows_any(&self) -> bool {
+        self.0.origins.iter().any(|pattern| pattern == "*") && !self.0.credentials
+    }
+
+    /// The value for `Access-Control-Allow-Origin`, if `origin` is allowed.
+    fn allow_origin(&self, origin: &str) -> Option<String> {
+        if self.allows_any() {
+            return Some("*".to_string());
+        }
+        self.0
+            .origins
+            .iter()
+            .any(|pattern| matches_origin(pattern, origin))
+            .then(|| origin.to_string())
+    }
+}
+
+/// Matches an origin against `*`, an exact origin or a pattern with one `*`
+/// standing in for subdomains, such as `https://*.example.dk`.
+fn matches_origin(pattern: &str, origin: &str) -> bool {
+    match pattern.split_once('*') {
+        None => pattern.eq_ignore_ascii_case(origin),
+        Some(("", "")) => true,
+        Some((prefix, suffix)) => {
+            let origin = origin.to_ascii_lowercase();
+            origin.len() > prefix.len() + suffix.len()
+                && origin.starts_with(&prefix.to_ascii_lowercase())
+                && origin.ends_with(&suffix.to_ascii_lowercase())
+                && !origin[prefix.len()..origin.len() - suffix.len()].contains(['/', ':'])
+        }
+    }
+}
+
+#[options("/<_..>")]
+fn preflight() -> Status {
+    Status::NoContent
+}
+
+#[rocket::async_trait]
+impl Fairing for Cors {
+    fn info(&self) -> Info {
+        Info {
+            name: "CORS",
+            kind: Kind::Ignite | Kind::Response,
+        }
+    }
+
+    async fn on_ignite(&self, rocket: Rocket<Build>) -> fairing::Result {
+        Ok(rocket.mount("/", routes![preflight]))
+    }
+
+    async fn on_response<'r>(&self, request: &'r Request<'_>, response: &mut Response<'r>) {
+        // Also without or with a disallowed Origin, so caches don't hand
+        // that response to an allowed origin
+        if !self.allows_any() {
+            response.adjoin_header(Header::new("Vary", "Origin"));
+        }
+        let Some(origin) = request.headers().get_one("Origin") else {
+            return;
+        };
+        let Some(allowed) = self.allow_origin(origin) else {
+            return;
+        };
+        response.set_header(Header::new("Access-Control-Allow-Origin", allowed));
+        if self.0.credentials {
+            response.set_header(Header::new("Access-Control-Allow-Credentials", "true"));
+        }
+
+        if request.method() == Method::Options {
+            response.set_header(Header::new("Access-Control-Allow-Methods", ALLOWED_METHODS));
+            if let Some(headers) = request.headers().get_one("Access-Control-Request-Headers") {
+                response.set_header(Header::new(
+                    "Access-Control-Allow-Headers",
+                    headers.to_string(),
+                ));
+            }
+            response.set_header(Header::new(
+                "Access-Control-Max-Age",
+                self.0.max_age.to_string(),
+            ));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rocket::{http::Header, local::blocking::Client};
+
+    use super::*;
+
+    #[get("/")]
+    fn index() -> &'static str {
+        "ok"
+    }
+
+    fn client(origins: &[&str], credentials: bool) -> Client {
+        let settings = CorsSettings {
+            origins: origins.iter().map(|origin| origin.to_string()).collect(),
+            credentials,
+            max_age: 600,
+        };
+        let rocket = rocket::build()
+            .attach(Cors(settings))
+            .mount("/", routes![index]);
+        Client::untracked(rocket).unwrap()
+    }
+
+    #[test]
+    fn test_matches_origin() {
+        assert!(matches_origin("*", "https://example.dk"));
+        assert!(matches_origin("https://example.dk", "https://EXAMPLE.dk"));
+        assert!(matches_origin(
+            "https://*.example.dk",
+            "https://www.example.dk"
+        ));
+        assert!(!matches_origin(
+            "https://*.example.dk",
+            "https://example.dk"
+        ));
+        assert!(!matches_origin(
+            "https://*.example.dk",
+            "https://evil.dk/.example.dk"
+        ));
+        assert!(!matches_origin(
+            "https://*.example.dk",
+            "http://www.example.dk"
+        ));
+    }
+
+    #[test]
+    fn test_wildcard() {
+        let client = client(&["*"], false);
+        let response = client
+            .get("/")
+            .header(Header::new("Origin", "https://example.dk"))
+            .dispatch();
+        let headers = response.headers();
+        assert_eq!(headers.get_one("Access-Control-Allow-Origin"), Some("*"));
+        assert_eq!(headers.get_one("Access-Control-Allow-Credentials"), None);
+
+        let response = client.get("/").dispatch();
+        assert_eq!(
+            response.headers().get_one("Access-Control-Allow-Origin"),
+            None
+        );
+        assert_eq!(response.headers().get_one("Vary"), None);
+    }
+
+    #[test]
+    fn test_echoes_allowed_origin() {
+        let client = client(&["https://*.example.dk"], true);
+        let response = client
+            .get("/")
+            .header(Header::new("Origin", "https://kort.example.dk"))
+            .dispatch();
+        let headers = response.headers();
+        assert_eq!(
+            headers.get_one("Access-Control-Allow-Origin"),
+            Some("https://kort.example.dk")
+        );
+        assert_eq!(
+            headers.get_one("Access-Control-Allow-Credentials"),
+            Some("true")
+        );
+        assert_eq!(headers.get_one("Vary"), Some("Origin"));
+
+        let response = client
+            .get("/")
+            .header(Header::new("Origin", "https://example.com"))
+            .dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(
+            response.headers().get_one("Access-Control-Allow-Origin"),
+            None
+        );
+        assert_eq!(response.headers().get_one("Vary"), Some("Origin"));
+
+        let response = client.get("/").dispatch();
+        assert_eq!(response.headers().get_one("Vary"), Some("Origin"));
+    }
+
+    #[test]
+    fn test_preflight() {
+        let client = client(&["https://example.dk"], false);
+        let response = client
+            .options("/adresser")
+            .header(Header::new("Origin", "https://example.dk"))
+            .header(Header::new("Access-Control-Request-Method", "POST"))
+            .header(Header::new(
+                "Access-Control-Request-Headers",
+                "content-type",
+            ))
+            .dispatch();
+        assert_eq!(response.status(), Status::NoContent);
+        let headers = response.headers();
+        assert_eq!(
+            headers.get_one("Access-Control-Allow-Origin"),
+            Some("https://example.dk")
+        );
+        assert_eq!(
+            headers.get_one("Access-Control-Allow-Methods"),
+            Some(ALLOWED_METHODS)
+        );
+        assert_eq!(
+            headers.get_one("Access-Control-Allow-Headers"),
+            Some("content-type")
+        );
+        assert_eq!(headers.get_one("Access-Control-Max-Age"), Some("600"));
+    }
+}
@@ -0,0 +1,186 @@
+use std::{
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, SystemTime},
+};
+
+use arc_swap::ArcSwap;
+use log::{error, info, warn};
+use rocket::{
+    fairing::{Fairing, Info, Kind},
+    tokio::{self, task},
+    Orbit, Rocket,
+};
+
+use crate::address_completer::AddressCompleter;
+use crate::config::Settings;
+
+/// The address data being served. A reload builds new indexes in the
+/// background and swaps them in when ready, requests already running keep
+/// the data they started with.
+pub struct Dataset {
+    current: ArcSwap<AddressCompleter>,
+    settings: Settings,
+    reloading: AtomicBool,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ReloadError {
+    /// Another reload is running
+    Busy,
+    /// Building the new indexes failed, the old ones are still served
+    Failed(String),
+}
+
+impl Dataset {
+    pub fn new(settings: Settings, completer: AddressCompleter) -> Dataset {
+        Dataset {
+            current: ArcSwap::from_pointee(completer),
+            settings,
+            reloading: AtomicBool::new(false),
+        }
+    }
+
+    pub fn current(&self) -> Arc<AddressCompleter> {
+        self.current.load_full()
+    }
+
+    pub fn replace(&self, completer: AddressCompleter) {
+        self.current.store(Arc::new(completer));
+    }
+
+    /// Builds the indexes again from the data files and swaps them in,
+    /// returning the number of addresses loaded.
+    pub async fn reload(self: Arc<Self>) -> Result<usize, ReloadError> {
+        if self.reloading.swap(true, Ordering::AcqRel) {
+            return Err(ReloadError::Busy);
+        }
+        info!("Reloading addresses");
+        let dataset = self.clone();
+        let built = task::spawn_blocking(move || AddressCompleter::init(&dataset.settings)).await;
+        self.reloading.store(false, Ordering::Release);
+
+        match built {
+            Ok(completer) => {
+                let count = completer.address_count();
+                self.replace(completer);
+                info!("Reloaded {} addresses", count);
+                Ok(count)
+            }
+            Err(error) => {
+                let message = match error.try_into_panic() {
+                    Ok(panic) => panic
+                        .downcast_ref::<String>()
+                        .cloned()
+                        .or_else(|| panic.downcast_ref::<&str>().map(|s| s.to_string()))
+                        .unwrap_or_else(|| "reload panicked".to_string()),
+                    Err(error) => error.to_string(),
+                };
+                error!("Reload failed, keeping the old addresses: {}", message);
+                Err(ReloadError::Failed(message))
+            }
+        }
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path)
+        .and_then(|meta| meta.modified())
+        .ok()
+}
+
+/// Reloads the dataset on `SIGHUP` and, when `reload.watch` is set, when the
+/// address files change.
+pub struct Reload;
+
+#[rocket::async_trait]
+impl Fairing for Reload {
+    fn info(&self) -> Info {
+        Info {
+            name: "Reload",
+            kind: Kind::Liftoff,
+        }
+    }
+
+    async fn on_liftoff(&self, rocket: &Rocket<Orbit>) {
+        let (Some(dataset), Some(settings)) =
+            (rocket.state::<Arc<Dataset>>(), rocket.state::<Settings>())
+        else {
+            return;
+        };
+
+        #[cfg(unix)]
+        {
+            use tokio::signal::unix::{signal, SignalKind};
+            let dataset = dataset.clone();
+            match signal(SignalKind::hangup()) {
+                Ok(mut hangups) => {
+                    tokio::spawn(async move {
+                        while hangups.recv().await.is_some() {
+                            let _ = dataset.clone().reload().await;
+                        }
+                    });
+                }
+                Err(error) => warn!("Can't reload on SIGHUP: {}", error),
+            }
+        }
+
+        if settings.reload.watch {
+            let dataset = dataset.clone();
+            let files = [settings.data.addresses(), settings.data.dawa_export()];
+            let interval = Duration::from_secs(settings.reload.interval);
+            tokio::spawn(async move {
+                let mut seen = files.clone().map(|file| modified(&file));
+                let mut ticks = tokio::time::interval(interval);
+                ticks.tick().await;
+                loop {
+                    ticks.tick().await;
+                    let now = files.clone().map(|file| modified(&file));
+                    if now != seen {
+                        let _ = dataset.clone().reload().await;
+                        // Converting the export rewrites the address file
+                        seen = files.clone().map(|file| modified(&file));
+                    }
+                }
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    fn settings(dir: &Path) -> Settings {
+        let mut settings = Settings::default();
+        settings.data.dir = dir.to_path_buf();
+        settings
+    }
+
+    #[rocket::async_test]
+    async fn test_reload_swaps_and_keeps_old() {
+        let dir = std::env::temp_dir().join(format!("dawa-reload-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let fixtures = PathBuf::from(concat!(env!("CARGO_MANIFEST_DIR"), "/fixtures"));
+        std::fs::copy(fixtures.join("address.csv"), dir.join("address.csv")).unwrap();
+
+        let dataset = Arc::new(Dataset::new(settings(&dir), AddressCompleter::new()));
+        let old = dataset.current();
+        let count = dataset.clone().reload().await.unwrap();
+        assert!(count > 0);
+        assert_eq!(old.address_count(), 0);
+        assert_eq!(dataset.current().address_count(), count);
+
+        std::fs::write(dir.join("address.csv"), "not,an\naddress,file\n").unwrap();
+        let error = dataset.clone().reload().await.unwrap_err();
+        assert!(matches!(error, ReloadError::Failed(_)));
+        assert_eq!(dataset.current().address_count(), count);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
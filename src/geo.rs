@@ -0,0 +1,608 @@
+use std::{cmp::Ordering, str::FromStr};
+
+use serde_json::Value;
+
+use crate::size_of::SizeOf;
+
+const EARTH_RADIUS: f64 = 6_371_008.8;
+const METERS_PER_DEGREE: f64 = 111_320.0;
+/// The largest `cirkel` radius accepted, in meters
+const MAX_RADIUS: f64 = 1_000_000.0;
+
+/// GRS80, the ellipsoid of ETRS89
+const SEMI_MAJOR_AXIS: f64 = 6_378_137.0;
+const FLATTENING: f64 = 1.0 / 298.257_222_101;
+/// UTM zone 32
+const CENTRAL_MERIDIAN: f64 = 9.0;
+const SCALE_FACTOR: f64 = 0.9996;
+const FALSE_EASTING: f64 = 500_000.0;
+
+/// A WGS84 position, `x` being longitude and `y` latitude as in DAWA.
+#[derive(Debug, Default, Copy, Clone)]
+pub struct Point {
+    pub x: f64,
+    pub y: f64,
+}
+
+impl Point {
+    pub fn new(x: f64, y: f64) -> Point {
+        Point { x, y }
+    }
+
+    /// Whether this is a longitude and latitude, false for NaN.
+    pub fn is_in_range(&self) -> bool {
+        (-180.0..=180.0).contains(&self.x) && (-90.0..=90.0).contains(&self.y)
+    }
+
+    /// Great circle distance in meters.
+    pub fn distance(&self, other: &Point) -> f64 {
+        let (lat1, lat2) = (self.y.to_radians(), other.y.to_radians());
+        let dlat = lat2 - lat1;
+        let dlon = (other.x - self.x).to_radians();
+        let a = (dlat / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * (dlon / 2.0).sin().powi(2);
+        2.0 * EARTH_RADIUS * a.sqrt().asin()
+    }
+
+    /// The position in ETRS89 / UTM zone 32N, `x` being easting and `y`
+    /// northing in meters. WGS84 and ETRS89 differ by less than a meter in
+    /// Denmark, which is ignored. Krüger's series, accurate to a millimeter.
+    pub fn to_utm32(self) -> Point {
+        let (n, a) = rectifying_radius();
+        let alpha = [
+            n / 2.0 - 2.0 * n.powi(2) / 3.0 + 5.0 * n.powi(3) / 16.0,
+            13.0 * n.powi(2) / 48.0 - 3.0 * n.powi(3) / 5.0,
+            61.0 * n.powi(3) / 240.0,
+        ];
+        let e = 2.0 * n.sqrt() / (1.0 + n);
+        let latitude = self.y.to_radians();
+        let longitude = (self.x - CENTRAL_MERIDIAN).to_radians();
+        let t = (latitude.sin().atanh() - e * (e * latitude.sin()).atanh()).sinh();
+        let xi = t.atan2(longitude.cos());
+        let eta = (longitude.sin() / (1.0 + t * t).sqrt()).atanh();
+        let (mut easting, mut northing) = (eta, xi);
+        for (j, alpha) in alpha.iter().enumerate() {
+            let k = 2.0 * (j + 1) as f64;
+            easting += alpha * (k * xi).cos() * (k * eta).sinh();
+            northing += alpha * (k * xi).sin() * (k * eta).cosh();
+        }
+        Point::new(
+            FALSE_EASTING + SCALE_FACTOR * a * easting,
+            SCALE_FACTOR * a * northing,
+        )
+    }
+
+    /// The WGS84 position of ETRS89 / UTM zone 32N coordinates, the inverse
+    /// of `to_utm32`.
+    pub fn from_utm32(utm: Point) -> Point {
+        let (n, a) = rectifying_radius();
+        let beta = [
+            n / 2.0 - 2.0 * n.powi(2) / 3.0 + 37.0 * n.powi(3) / 96.0,
+            n.powi(2) / 48.0 + n.powi(3) / 15.0,
+            17.0 * n.powi(3) / 480.0,
+        ];
+        let delta = [
+            2.0 * n - 2.0 * n.powi(2) / 3.0 - 2.0 * n.powi(3),
+            7.0 * n.powi(2) / 3.0 - 8.0 * n.powi(3) / 5.0,
+            56.0 * n.powi(3) / 15.0,
+        ];
+        let xi = utm.y / (SCALE_FACTOR * a);
+        let eta = (utm.x - FALSE_EASTING) / (SCALE_FACTOR * a);
+        let (mut xi_prime, mut eta_prime) = (xi, eta);
+        for (j, beta) in beta.iter().enumerate() {
+            let k = 2.0 * (j + 1) as f64;
+            xi_prime -= beta * (k * xi).sin() * (k * eta).cosh();
+            eta_prime -= beta * (k * xi).cos() * (k * eta).sinh();
+        }
+        let chi = (xi_prime.sin() / eta_prime.cosh()).asin();
+        let mut latitude = chi;
+        for (j, delta) in delta.iter().enumerate() {
+            latitude += delta * (2.0 * (j + 1) as f64 * chi).sin();
+        }
+        let longitude = eta_prime.sinh().atan2(xi_prime.cos());
+        Point::new(
+            CENTRAL_MERIDIAN + longitude.to_degrees(),
+            latitude.to_degrees(),
+        )
+    }
+}
+
+/// The third flattening and the radius of the rectifying sphere of GRS80.
+fn rectifying_radius() -> (f64, f64) {
+    let n = FLATTENING / (2.0 - FLATTENING);
+    let a = SEMI_MAJOR_AXIS / (1.0 + n) * (1.0 + n.powi(2) / 4.0 + n.powi(4) / 64.0);
+    (n, a)
+}
+
+/// The `srid` parameter: the coordinate system of coordinates in requests and
+/// responses.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum Srid {
+    /// Longitude and latitude
+    #[default]
+    Wgs84,
+    /// Easting and northing in UTM zone 32N
+    Etrs89,
+}
+
+impl Srid {
+    pub fn code(&self) -> u32 {
+        match self {
+            Srid::Wgs84 => 4326,
+            Srid::Etrs89 => 25832,
+        }
+    }
+
+    /// A WGS84 position in this coordinate system, UTM rounded to centimeters
+    /// as DAWA does.
+    pub fn project(&self, point: Point) -> Point {
+        match self {
+            Srid::Wgs84 => point,
+            Srid::Etrs89 => {
+                let point = point.to_utm32();
+                Point::new(
+                    (point.x * 100.0).round() / 100.0,
+                    (point.y * 100.0).round() / 100.0,
+                )
+            }
+        }
+    }
+
+    /// A position in this coordinate system in WGS84.
+    pub fn unproject(&self, point: Point) -> Point {
+        match self {
+            Srid::Wgs84 => point,
+            Srid::Etrs89 => Point::from_utm32(point),
+        }
+    }
+}
+
+impl FromStr for Srid {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "4326" => Ok(Srid::Wgs84),
+            "25832" => Ok(Srid::Etrs89),
+            _ => Err(format!(
+                "{} er ikke en understøttet srid, brug 4326 eller 25832",
+                s
+            )),
+        }
+    }
+}
+
+impl PartialEq for Point {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Point {}
+
+impl PartialOrd for Point {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Point {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.x
+            .total_cmp(&other.x)
+            .then_with(|| self.y.total_cmp(&other.y))
+    }
+}
+
+impl SizeOf for Point {
+    fn size_of(&self) -> usize {
+        0
+    }
+}
+
+/// `cirkel=x,y,radius` with the radius in meters.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Circle {
+    pub center: Point,
+    pub radius: f64,
+}
+
+impl Circle {
+    pub fn contains(&self, point: &Point) -> bool {
+        self.center.distance(point) <= self.radius
+    }
+
+    pub fn bounding_box(&self) -> BoundingBox {
+        let dy = self.radius / METERS_PER_DEGREE;
+        let dx = dy / self.center.y.to_radians().cos().max(0.01);
+        BoundingBox {
+            min: Point::new(self.center.x - dx, self.center.y - dy),
+            max: Point::new(self.center.x + dx, self.center.y + dy),
+        }
+    }
+}
+
+impl FromStr for Circle {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts = s
+            .split(',')
+            .map(|part| part.trim().parse::<f64>())
+            .collect::<Result<Vec<f64>, _>>()
+            .map_err(|_| format!("{} is not a valid circle, expected x,y,radius", s))?;
+        if parts.iter().any(|part| !part.is_finite()) {
+            return Err(format!("{} is not a valid circle, expected x,y,radius", s));
+        }
+        match parts[..] {
+            [x, y, radius] if (0.0..=MAX_RADIUS).contains(&radius) => Ok(Circle {
+                center: Point::new(x, y),
+                radius,
+            }),
+            _ => Err(format!("{} is not a valid circle, expected x,y,radius", s)),
+        }
+    }
+}
+
+/// `bbox=minx,miny,maxx,maxy`
+#[derive(Debug, Clone, PartialEq)]
+pub struct BoundingBox {
+    pub min: Point,
+    pub max: Point,
+}
+
+impl BoundingBox {
+    pub fn contains(&self, point: &Point) -> bool {
+        point.x >= self.min.x
+            && point.x <= self.max.x
+            && point.y >= self.min.y
+            && point.y <= self.max.y
+    }
+
+    fn extend(&mut self, point: &Point) {
+        self.min = Point::new(self.min.x.min(point.x), self.min.y.min(point.y));
+        self.max = Point::new(self.max.x.max(point.x), self.max.y.max(point.y));
+    }
+}
+
+impl FromStr for BoundingBox {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let error = || format!("{} is not a valid bbox, expected minx,miny,maxx,maxy", s);
+        let parts = s
+            .split(',')
+            .map(|part| part.trim().parse::<f64>())
+            .collect::<Result<Vec<f64>, _>>()
+            .map_err(|_| error())?;
+        if parts.iter().any(|part| !part.is_finite()) {
+            return Err(error());
+        }
+        match parts[..] {
+            [minx, miny, maxx, maxy] if minx <= maxx && miny <= maxy => Ok(BoundingBox {
+                min: Point::new(minx, miny),
+                max: Point::new(maxx, maxy),
+            }),
+            _ => Err(error()),
+        }
+    }
+}
+
+/// A polygon given as GeoJSON rings, the first ring being the exterior and
+/// any following rings holes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Polygon {
+    pub rings: Vec<Vec<Point>>,
+}
+
+impl Polygon {
+    pub fn contains(&self, point: &Point) -> bool {
+        match self.rings.split_first() {
+            Some((exterior, holes)) => {
+                ring_contains(exterior, point)
+                    && !holes.iter().any(|hole| ring_contains(hole, point))
+            }
+            None => false,
+        }
+    }
+}
+
+/// Even-odd ray casting.
+fn ring_contains(ring: &[Point], point: &Point) -> bool {
+    let mut inside = false;
+    let mut j = ring.len().wrapping_sub(1);
+    for i in 0..ring.len() {
+        let (a, b) = (&ring[i], &ring[j]);
+        if (a.y > point.y) != (b.y > point.y)
+            && point.x < (b.x - a.x) * (point.y - a.y) / (b.y - a.y) + a.x
+        {
+            inside = !inside;
+        }
+        j = i;
+    }
+    inside
+}
+
+/// `polygon=` accepts both GeoJSON Polygon and MultiPolygon coordinates.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MultiPolygon {
+    pub polygons: Vec<Polygon>,
+}
+
+impl MultiPolygon {
+    pub fn contains(&self, point: &Point) -> bool {
+        self.polygons.iter().any(|polygon| polygon.contains(point))
+    }
+
+    pub fn bounding_box(&self) -> BoundingBox {
+        let mut points = self
+            .polygons
+            .iter()
+            .flat_map(|polygon| polygon.rings.first())
+            .flatten();
+        let first = points.next().copied().unwrap_or_default();
+        let mut bounding_box = BoundingBox {
+            min: first,
+            max: first,
+        };
+        for point in points {
+            bounding_box.extend(point);
+        }
+        bounding_box
+    }
+}
+
+impl FromStr for MultiPolygon {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let value: Value = serde_json::from_str(s)
+            .map_err(|_| format!("{} is not valid GeoJSON coordinates", s))?;
+        let polygons = if value[0][0][0].is_number() {
+            vec![parse_polygon(&value)?]
+        } else {
+            value
+                .as_array()
+                .ok_or("polygon must be an array")?
+                .iter()
+                .map(parse_polygon)
+                .collect::<Result<Vec<Polygon>, String>>()?
+        };
+        if polygons.is_empty() {
+            return Err("polygon must contain at least one ring".to_string());
+        }
+        Ok(MultiPolygon { polygons })
+    }
+}
+
+fn parse_polygon(value: &Value) -> Result<Polygon, String> {
+    let rings = value
+        .as_array()
+        .ok_or("polygon must be an array of rings")?
+        .iter()
+        .map(parse_ring)
+        .collect::<Result<Vec<Vec<Point>>, String>>()?;
+    if rings.is_empty() {
+        return Err("polygon must contain at least one ring".to_string());
+    }
+    Ok(Polygon { rings })
+}
+
+fn parse_ring(value: &Value) -> Result<Vec<Point>, String> {
+    let ring = value
+        .as_array()
+        .ok_or("ring must be an array of positions")?
+        .iter()
+        .map(
+            |position| match (position[0].as_f64(), position[1].as_f64()) {
+                (Some(x), Some(y)) if x.is_finite() && y.is_finite() => Ok(Point::new(x, y)),
+                _ => Err(format!("{} is not a valid position", position)),
+            },
+        )
+        .collect::<Result<Vec<Point>, String>>()?;
+    if ring.len() < 4 {
+        return Err("ring must contain at least four positions".to_string());
+    }
+    Ok(ring)
+}
+
+/// The area a search is restricted to.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Area {
+    Circle(Circle),
+    BoundingBox(BoundingBox),
+    Polygon(MultiPolygon),
+}
+
+impl Area {
+    pub fn contains(&self, point: &Point) -> bool {
+        match self {
+            Area::Circle(circle) => circle.contains(point),
+            Area::BoundingBox(bounding_box) => bounding_box.contains(point),
+            Area::Polygon(polygon) => polygon.contains(point),
+        }
+    }
+
+    /// The area given in `srid` in WGS84. A bounding box in UTM is no
+    /// rectangle in WGS84, so it becomes a polygon.
+    pub fn unproject(self, srid: Srid) -> Area {
+        if srid == Srid::Wgs84 {
+            return self;
+        }
+        let unproject = |points: &[Point]| -> Vec<Point> {
+            points.iter().map(|point| srid.unproject(*point)).collect()
+        };
+        match self {
+            Area::Circle(circle) => Area::Circle(Circle {
+                center: srid.unproject(circle.center),
+                radius: circle.radius,
+            }),
+            Area::BoundingBox(BoundingBox { min, max }) => Area::Polygon(MultiPolygon {
+                polygons: vec![Polygon {
+                    rings: vec![unproject(&[
+                        min,
+                        Point::new(max.x, min.y),
+                        max,
+                        Point::new(min.x, max.y),
+                        min,
+                    ])],
+                }],
+            }),
+            Area::Polygon(polygon) => Area::Polygon(MultiPolygon {
+                polygons: polygon
+                    .polygons
+                    .iter()
+                    .map(|polygon| Polygon {
+                        rings: polygon.rings.iter().map(|ring| unproject(ring)).collect(),
+                    })
+                    .collect(),
+            }),
+        }
+    }
+
+    /// Whether every position is a longitude and latitude, as they must be
+    /// once unprojected.
+    pub fn is_in_range(&self) -> bool {
+        match self {
+            Area::Circle(circle) => circle.center.is_in_range(),
+            Area::BoundingBox(bounding_box) => {
+                bounding_box.min.is_in_range() && bounding_box.max.is_in_range()
+            }
+            Area::Polygon(polygon) => polygon
+                .polygons
+                .iter()
+                .flat_map(|polygon| &polygon.rings)
+                .flatten()
+                .all(Point::is_in_range),
+        }
+    }
+
+    pub fn bounding_box(&self) -> BoundingBox {
+        match self {
+            Area::Circle(circle) => circle.bounding_box(),
+            Area::BoundingBox(bounding_box) => bounding_box.clone(),
+            Area::Polygon(polygon) => polygon.bounding_box(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_distance() {
+        let holeby = Point::new(11.45702023, 54.71182365);
+        let valby = Point::new(12.48971377, 55.667307);
+        let distance = holeby.distance(&valby);
+        assert!((distance - 125_000.0).abs() < 2_000.0, "{}", distance);
+        assert_eq!(holeby.distance(&holeby), 0.0);
+    }
+
+    #[test]
+    fn test_to_utm32() {
+        let valby = Point::new(12.48971377, 55.667307).to_utm32();
+        assert!((valby.x - 719_462.430).abs() < 0.001, "{:?}", valby);
+        assert!((valby.y - 6_174_574.559).abs() < 0.001, "{:?}", valby);
+        let central = Point::new(9.0, 56.0).to_utm32();
+        assert!((central.x - 500_000.0).abs() < 0.001, "{:?}", central);
+        assert!((central.y - 6_206_079.587).abs() < 0.001, "{:?}", central);
+        assert_eq!("25832".parse(), Ok(Srid::Etrs89));
+        let back = Point::from_utm32(Point::new(719_462.430, 6_174_574.559));
+        assert!((back.x - 12.48971377).abs() < 1e-7, "{:?}", back);
+        assert!((back.y - 55.667307).abs() < 1e-7, "{:?}", back);
+        assert!("3857".parse::<Srid>().is_err());
+    }
+
+    #[test]
+    fn test_parse_circle() {
+        let circle: Circle = "12.5,55.6,100".parse().unwrap();
+        assert_eq!(circle.center, Point::new(12.5, 55.6));
+        assert_eq!(circle.radius, 100.0);
+        assert!("12.5,55.6".parse::<Circle>().is_err());
+        assert!("12.5,55.6,-1".parse::<Circle>().is_err());
+        assert!("a,b,c".parse::<Circle>().is_err());
+        assert!("12.5,55.6,inf".parse::<Circle>().is_err());
+        assert!("NaN,55.6,100".parse::<Circle>().is_err());
+        assert!("12.5,55.6,1e7".parse::<Circle>().is_err());
+    }
+
+    #[test]
+    fn test_circle_contains() {
+        let circle: Circle = "12.5,55.6,1000".parse().unwrap();
+        assert!(circle.contains(&Point::new(12.505, 55.6)));
+        assert!(!circle.contains(&Point::new(12.6, 55.6)));
+    }
+
+    #[test]
+    fn test_parse_bounding_box() {
+        let bounding_box: BoundingBox = "12.4,55.6,12.6,55.7".parse().unwrap();
+        assert!(bounding_box.contains(&Point::new(12.5, 55.65)));
+        assert!(!bounding_box.contains(&Point::new(12.7, 55.65)));
+        assert!("12.6,55.6,12.4,55.7".parse::<BoundingBox>().is_err());
+        assert!("12.4,55.6,12.6".parse::<BoundingBox>().is_err());
+        assert!("-inf,55.6,12.6,55.7".parse::<BoundingBox>().is_err());
+    }
+
+    #[test]
+    fn test_polygon_with_hole() {
+        let polygon: MultiPolygon =
+            "[[[0,0],[10,0],[10,10],[0,10],[0,0]],[[4,4],[6,4],[6,6],[4,6],[4,4]]]"
+                .parse()
+                .unwrap();
+        assert_eq!(polygon.polygons.len(), 1);
+        assert!(polygon.contains(&Point::new(2.0, 2.0)));
+        assert!(!polygon.contains(&Point::new(5.0, 5.0)));
+        assert!(!polygon.contains(&Point::new(11.0, 5.0)));
+    }
+
+    #[test]
+    fn test_multipolygon() {
+        let polygon: MultiPolygon =
+            "[[[[0,0],[1,0],[1,1],[0,1],[0,0]]],[[[5,5],[6,5],[6,6],[5,6],[5,5]]]]"
+                .parse()
+                .unwrap();
+        assert_eq!(polygon.polygons.len(), 2);
+        assert!(polygon.contains(&Point::new(0.5, 0.5)));
+        assert!(polygon.contains(&Point::new(5.5, 5.5)));
+        assert!(!polygon.contains(&Point::new(3.0, 3.0)));
+        assert_eq!(
+            polygon.bounding_box(),
+            BoundingBox {
+                min: Point::new(0.0, 0.0),
+                max: Point::new(6.0, 6.0)
+            }
+        );
+    }
+
+    #[test]
+    fn test_unproject_area() {
+        let valby = Point::new(12.48971377, 55.667307);
+        let bounding_box: BoundingBox = "719400,6174500,719500,6174600".parse().unwrap();
+        let area = Area::BoundingBox(bounding_box).unproject(Srid::Etrs89);
+        assert!(matches!(area, Area::Polygon(_)));
+        assert!(area.contains(&valby));
+        assert!(area.bounding_box().contains(&valby));
+
+        let circle: Circle = "719462,6174574,10".parse().unwrap();
+        let area = Area::Circle(circle).unproject(Srid::Etrs89);
+        assert!(area.contains(&valby));
+        let circle: Circle = "719462,6174600,10".parse().unwrap();
+        assert!(!Area::Circle(circle)
+            .unproject(Srid::Etrs89)
+            .contains(&valby));
+
+        assert!(Area::BoundingBox("12,55,13,56".parse().unwrap()).is_in_range());
+        assert!(!Area::BoundingBox("-1e300,-1e300,1e300,1e300".parse().unwrap()).is_in_range());
+        let bounding_box: BoundingBox = "-1e300,-1e300,1e300,1e300".parse().unwrap();
+        assert!(!Area::BoundingBox(bounding_box)
+            .unproject(Srid::Etrs89)
+            .is_in_range());
+    }
+
+    #[test]
+    fn test_parse_invalid_polygon() {
+        assert!("[[[0,0],[1,0],[0,0]]]".parse::<MultiPolygon>().is_err());
+        assert!("[]".parse::<MultiPolygon>().is_err());
+        assert!("not json".parse::<MultiPolygon>().is_err());
+    }
+}
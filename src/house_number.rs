@@ -0,0 +1,82 @@
+use std::{fmt, str::FromStr};
+
+use rocket::serde::{Serialize, Serializer};
+
+/// A husnummer such as `12` or `12B`. Orders naturally, `2` before `10` and
+/// `12` before `12A` before `12B`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct HouseNumber {
+    pub number: u16,
+    pub letter: Option<char>,
+}
+
+impl HouseNumber {
+    pub fn new(number: u16, letter: Option<char>) -> HouseNumber {
+        HouseNumber { number, letter }
+    }
+}
+
+impl FromStr for HouseNumber {
+    type Err = String;
+
+    /// Parses DAWA's `husnr`, ignoring case and space before the letter.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let digits = s.len() - s.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+        let number = s[..digits]
+            .parse()
+            .map_err(|_| format!("{} er ikke et gyldigt husnummer", s))?;
+        let mut rest = s[digits..].trim_start().chars();
+        match (rest.next(), rest.next()) {
+            (None, _) => Ok(HouseNumber::new(number, None)),
+            (Some(letter), None) if letter.is_alphabetic() => {
+                Ok(HouseNumber::new(number, letter.to_uppercase().next()))
+            }
+            _ => Err(format!("{} er ikke et gyldigt husnummer", s)),
+        }
+    }
+}
+
+impl fmt::Display for HouseNumber {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.number)?;
+        if let Some(letter) = self.letter {
+            write!(f, "{}", letter)?;
+        }
+        Ok(())
+    }
+}
+
+impl Serialize for HouseNumber {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!("12".parse(), Ok(HouseNumber::new(12, None)));
+        assert_eq!("12b".parse(), Ok(HouseNumber::new(12, Some('B'))));
+        assert_eq!(" 12 B".parse(), Ok(HouseNumber::new(12, Some('B'))));
+        assert!("".parse::<HouseNumber>().is_err());
+        assert!("B".parse::<HouseNumber>().is_err());
+        assert!("12BC".parse::<HouseNumber>().is_err());
+        assert!("99999".parse::<HouseNumber>().is_err());
+        assert_eq!(HouseNumber::new(12, Some('B')).to_string(), "12B");
+    }
+
+    #[test]
+    fn test_natural_order() {
+        let mut numbers: Vec<HouseNumber> = ["10", "2", "12B", "1", "12", "12A"]
+            .iter()
+            .map(|number| number.parse().unwrap())
+            .collect();
+        numbers.sort();
+        let numbers: Vec<String> = numbers.iter().map(|number| number.to_string()).collect();
+        assert_eq!(numbers, ["1", "2", "10", "12", "12A", "12B"]);
+    }
+}
@@ -3,64 +3,131 @@
 #[macro_use]
 extern crate rocket;
 
-use address_completer::{QueryElement, SearchMode};
+use address_completer::{AccessAddressRef, AddressCompleter, Proximity, QueryElement, SearchMode};
+use backends::r#static::StaticBackend;
+use config::{BackendKind, LoggingSettings, Settings};
+use cors::Cors;
+use dataset::{Dataset, Reload};
 // use dawa_autocomplete::size_of::SizeOf;
+use clap::Parser;
 use rocket::State;
 use rocket::{
-    fairing::{Fairing, Info, Kind},
-    http::{Header, Status},
+    figment::Figment,
+    http::{RawStr, Status},
+    local::blocking::Client,
     serde::json::Value,
-    Request, Response,
+    Build, Rocket,
 };
 use serde_json::json;
+use std::{
+    error::Error,
+    fs::File,
+    io::{BufReader, BufWriter},
+    process::ExitCode,
+    sync::Arc,
+    time::Instant,
+};
 
 mod address;
 mod address_completer;
+mod address_formatter;
+mod address_parser;
 mod backends;
+mod cli;
+mod config;
+mod cors;
+mod dataset;
+mod geo;
+mod house_number;
+mod matching;
+mod postal_code;
+mod replication;
+mod routes;
 pub mod size_of;
+mod spatial_index;
+mod street_index;
 mod token_index;
+mod unit;
+mod washing;
 
-struct Cors;
-
-#[rocket::async_trait]
-impl Fairing for Cors {
-    fn info(&self) -> Info {
-        Info {
-            name: "Add CORS headers to responses",
-            kind: Kind::Response,
-        }
-    }
-
-    async fn on_response<'r>(&self, _request: &'r Request<'_>, response: &mut Response<'r>) {
-        response.set_header(Header::new("Access-Control-Allow-Origin", "*"));
-        response.set_header(Header::new(
-            "Access-Control-Allow-Methods",
-            "POST, GET, PATCH, OPTIONS",
-        ));
-        response.set_header(Header::new("Access-Control-Allow-Headers", "*"));
-        response.set_header(Header::new("Access-Control-Allow-Credentials", "true"));
-    }
-}
 //GET https://dawa.aws.dk/autocomplete?q=kronprinsesse&type=adresse&caretpos=0&supplerendebynavn=true&stormodtagerpostnumre=true&multilinje=true&fuzzy=    => Vejnavn
 //GET https://dawa.aws.dk/autocomplete?q=maribovej 1&type=adresse&caretpos=11&supplerendebynavn=true&stormodtagerpostnumre=true&multilinje=true&fuzzy= => Adgangsadresse
 //GET https://dawa.aws.dk/autocomplete?q=Kronprinsesse Sofies Vej 1, st., 2000 Frederiksberg&type=adresse&caretpos=37&supplerendebynavn=true&stormodtagerpostnumre=true&multilinje=true&fuzzy=   => Adresse
-#[get("/autocomplete?<q>&<type>&<fuzzy>&<caretpos>&<per_side>&<startfra>&<adgangsadresseid>")]
+#[allow(clippy::too_many_arguments)]
+#[get("/autocomplete?<q>&<type>&<fuzzy>&<caretpos>&<side>&<per_side>&<startfra>&<adgangsadresseid>&<x>&<y>&<cirkel>&<polygon>&<bbox>&<srid>")]
 fn autocomplete(
-    completer: &State<address_completer::AddressCompleter>,
+    backend: routes::Backend,
+    settings: &State<Settings>,
+    href: routes::HrefBase,
     q: String,
     r#type: Option<String>,
     fuzzy: Option<String>,
     caretpos: Option<String>,
-    per_side: Option<i32>,
+    side: Option<String>,
+    per_side: Option<String>,
     startfra: Option<String>,
     adgangsadresseid: Option<String>,
+    x: Option<f64>,
+    y: Option<f64>,
+    cirkel: Option<String>,
+    polygon: Option<String>,
+    bbox: Option<String>,
+    srid: Option<String>,
 ) -> (Status, Value) {
     let mut result = Vec::new();
+    let srid: geo::Srid = match routes::parse_parameter("srid", srid) {
+        Ok(srid) => srid.unwrap_or_default(),
+        Err(error) => return error,
+    };
+    let proximity = Proximity {
+        near: match (x, y) {
+            (Some(x), Some(y)) => match srid.unproject(geo::Point::new(x, y)) {
+                near if near.is_in_range() => Some(near),
+                _ => {
+                    return routes::query_parameter_error(
+                        "x",
+                        "koordinaterne ligger uden for det gyldige område".to_string(),
+                    );
+                }
+            },
+            (None, None) => None,
+            _ => {
+                return routes::query_parameter_error(
+                    "x",
+                    "x og y skal angives sammen".to_string(),
+                );
+            }
+        },
+        within: match routes::parse_area(cirkel, polygon, bbox, srid) {
+            Ok(area) => area,
+            Err(error) => return error,
+        },
+    };
+    let page = match routes::Page::parse(side, per_side, &settings.search) {
+        Ok(page) => page,
+        Err(error) => return error,
+    };
+    // Suggestions are found up to the end of the page and the earlier ones
+    // skipped, so pages end within the first search.max_per_side
+    if page.end() > settings.search.max_per_side {
+        return routes::query_parameter_error(
+            "side",
+            format!(
+                "side * per_side må højst være {}",
+                settings.search.max_per_side
+            ),
+        );
+    }
+    let count = page.end();
     let query_element = QueryElement::from(&q);
 
     match query_element.get_search_mode(startfra, &adgangsadresseid) {
         SearchMode::Street => {
-            for street in completer.find_street(q, per_side.unwrap_or(50)) {
+            for street in backend
+                .complete_streets(&q, count, &proximity)
+                .into_iter()
+                .skip(page.offset())
+            {
                 result.push(json!(
                     {
                       "type": "vejnavn",
@@ -69,34 +136,26 @@ fn autocomplete(
                       "caretpos": street.len(),
                       "data": {
                         "navn": street,
-                        "href": format!("https://api.dataforsyningen.dk/vejnavne/{}", street)
+                        "href": routes::streets::href_for(&href.0, street.trim_end())
                       }
                     }
                 ));
             }
         }
         SearchMode::AccessAddress => {
-            for address in completer.find_access_address(q, per_side.unwrap_or(50)) {
+            for address in backend
+                .complete_access_addresses(&q, count, &proximity)
+                .into_iter()
+                .skip(page.offset())
+            {
+                let access_address = AccessAddressRef::from(&*address);
                 result.push(json!(
                     {
-                        "data": {
-                          "id": "58910400-b8f1-44bf-8293-7420ee1595a8",
-                          "status": 1,
-                          "darstatus": 3,
-                          "vejkode": "1013",
-                          "vejnavn": "Maribovej",
-                          "adresseringsvejnavn": "Maribovej",
-                          "husnr": "1",
-                          "supplerendebynavn": null,
-                          "postnr": "4960",
-                          "postnrnavn": "Holeby",
-                          "stormodtagerpostnr": null,
-                          "stormodtagerpostnrnavn": null,
-                          "kommunekode": "0360",
-                          "x": 11.45702023,
-                          "y": 54.71182365,
-                          "href": "https://api.dataforsyningen.dk/adgangsadresser/58910400-b8f1-44bf-8293-7420ee1595a8"
-                        },
+                        "data": suggestion_data(routes::access_addresses::mini(
+                            &access_address,
+                            srid,
+                            &href.0,
+                        )),
                         "stormodtagerpostnr": false,
                         "type": "adgangsadresse",
                         "tekst": address.access_address_name(),
@@ -107,30 +166,24 @@ fn autocomplete(
             }
         }
         SearchMode::Address => {
-            for address in completer.find_address(&q, &adgangsadresseid, per_side.unwrap_or(50)) {
+            let access_address_id =
+                match routes::parse_parameter("adgangsadresseid", adgangsadresseid) {
+                    Ok(Some(access_address_id)) => access_address_id,
+                    Ok(None) => return (Status::Ok, json!(result)),
+                    Err(error) => return error,
+                };
+            for address in backend
+                .complete_units(&q, &access_address_id, count)
+                .into_iter()
+                .skip(page.offset())
+            {
                 result.push(json!(
                     {
-                      "data": {
-                        "id": "0a3f509f-96d7-32b8-e044-0003ba298018",
-                        "status": 1,
-                        "darstatus": 3,
-                        "vejkode": "4640",
-                        "vejnavn": "Maribovej",
-                        "adresseringsvejnavn": "Maribovej",
-                        "husnr": "15",
-                        "etage": "st",
-                        "dør": "tv",
-                        "supplerendebynavn": null,
-                        "postnr": "2500",
-                        "postnrnavn": "Valby",
-                        "stormodtagerpostnr": null,
-                        "stormodtagerpostnrnavn": null,
-                        "kommunekode": "0101",
-                        "adgangsadresseid": "0a3f507a-c086-32b8-e044-0003ba298018",
-                        "x": 12.48971377,
-                        "y": 55.667307,
-                        "href": "https://api.dataforsyningen.dk/adresser/0a3f509f-96d7-32b8-e044-0003ba298018"
-                      },
+                      "data": suggestion_data(routes::addresses::mini(
+                          &address,
+                          srid,
+                          &href.0,
+                      )),
                       "stormodtagerpostnr": false,
                       "type": "adresse",
                       "tekst": address.display_name(),
@@ -146,14 +199,303 @@ fn autocomplete(
     (Status::Ok, json!(result))
 }
 
-#[launch]
-fn rocket() -> _ {
-    env_logger::init();
+/// The `data` of a suggestion: the mini document without `betegnelse`, as
+/// in DAWA.
+fn suggestion_data(mut document: Value) -> Value {
+    if let Some(fields) = document.as_object_mut() {
+        fields.shift_remove("betegnelse");
+    }
+    document
+}
+
+/// The server for the settings. The static backend has no data to load,
+/// search or reload, so only `/autocomplete` and the lookups its suggestions
+/// link to are mounted for it.
+fn rocket(
+    figment: Figment,
+    settings: Settings,
+    load: impl FnOnce(&Settings) -> AddressCompleter,
+) -> Rocket<Build> {
+    let rocket = rocket::custom(figment)
+        .attach(Cors(settings.cors.clone()))
+        .mount("/", routes![autocomplete]);
+    let rocket = match settings.search.backend {
+        BackendKind::Memory => rocket
+            .manage(Arc::new(Dataset::new(settings.clone(), load(&settings))))
+            .mount("/", routes::routes()),
+        BackendKind::Static => rocket
+            .manage(Arc::new(StaticBackend::new()))
+            .mount("/", routes::lookup_routes()),
+    };
+    rocket.manage(settings)
+}
+
+fn settings(figment: &Figment) -> Result<Settings, Box<dyn Error>> {
+    let settings: Settings = figment.extract()?;
+    settings.validate()?;
+    Ok(settings)
+}
+
+fn serve(figment: Figment, args: &cli::ServeArgs) -> Result<(), Box<dyn Error>> {
+    let mut figment = args.data.apply(figment);
+    if let Some(port) = args.port {
+        figment = figment.merge(("port", port));
+    }
+    if let Some(bind) = args.bind {
+        figment = figment.merge(("address", bind));
+    }
+    let settings = settings(&figment)?;
+    let address = rocket::Config::from(&figment).address;
+    if settings.href_base.is_none() && !address.is_loopback() {
+        warn!(
+            "href_base is not set, links will point at http://{}, which clients may not reach",
+            address
+        );
+    }
+    let backend = settings.search.backend;
+    let mut rocket = rocket(figment, settings, AddressCompleter::init);
+    if backend == BackendKind::Memory {
+        rocket = rocket.attach(Reload);
+    }
+    rocket::execute(rocket.launch())?;
+    Ok(())
+}
+
+fn convert(args: &cli::ConvertArgs) -> Result<(), Box<dyn Error>> {
+    if !args.input.exists() {
+        return Err(format!("{} not found", args.input.display()).into());
+    }
+    AddressCompleter::new().convert_from_dawa_export(&args.input, &args.output);
+    Ok(())
+}
+
+fn snapshot(figment: Figment, args: &cli::SnapshotArgs) -> Result<(), Box<dyn Error>> {
+    let settings = settings(&args.data.apply(figment))?;
+    let address_completer = AddressCompleter::init(&settings);
+    address_completer
+        .save(&args.output)
+        .map_err(|error| format!("Could not write {}: {}", args.output.display(), error))?;
+    Ok(())
+}
+
+/// Applies the replication events after the last applied sequence number
+/// and writes the address file, the pending rows and the sequence number
+/// back.
+fn update(figment: Figment, args: &cli::UpdateArgs) -> Result<(), Box<dyn Error>> {
+    let settings = settings(&args.data.apply(figment))?;
+    let source = args
+        .source
+        .as_deref()
+        .or(settings.replication.source.as_deref())
+        .map(replication::Source::from)
+        .ok_or("No replication source, set replication.source or pass --source")?;
+    let sequence_file = settings.data.dir.join(&settings.replication.sequence);
+    let from = match args.from {
+        Some(from) => from,
+        None => replication::read_sequence(&sequence_file)?.ok_or_else(|| {
+            format!(
+                "{} not found, pass --from with the sequence number the address file was exported at",
+                sequence_file.display()
+            )
+        })?,
+    };
 
-    let address_completer = address_completer::AddressCompleter::init();
+    let events = source.events(from)?;
+    let count = events.len();
+    let mut address_completer = AddressCompleter::init(&settings);
+    let pending_file = settings.data.dir.join(&settings.replication.pending);
+    let mut replica = replication::Replica::read(&pending_file)?;
+    let Some(last) = replica.apply(&mut address_completer, events)? else {
+        eprintln!("No changes since {}", from);
+        return Ok(());
+    };
 
-    rocket::build()
-        .attach(Cors)
-        .manage(address_completer)
-        .mount("/", routes![autocomplete])
+    let addresses = settings.data.addresses();
+    let temporary = addresses.with_extension("tmp");
+    address_completer
+        .save(&temporary)
+        .map_err(|error| format!("Could not write {}: {}", temporary.display(), error))?;
+    std::fs::rename(&temporary, &addresses)?;
+    replica.write(&pending_file, &address_completer)?;
+    replication::write_sequence(&sequence_file, last)?;
+    eprintln!("Applied {} changes, up to date with {}", count, last);
+    Ok(())
+}
+
+/// Runs the text through the `/autocomplete` route of a local server, so the
+/// output is exactly what a browser would get.
+fn query(figment: Figment, args: &cli::QueryArgs) -> Result<(), Box<dyn Error>> {
+    let figment = args.data.apply(figment);
+    let settings = settings(&figment)?;
+    let client = Client::untracked(rocket(figment, settings, AddressCompleter::init))?;
+    let mut uri = format!(
+        "/autocomplete?q={}",
+        RawStr::new(&args.text).percent_encode()
+    );
+    for (name, value) in [
+        ("startfra", args.startfra.clone()),
+        ("adgangsadresseid", args.adgangsadresseid.clone()),
+        (
+            "per_side",
+            args.per_side.map(|per_side| per_side.to_string()),
+        ),
+    ] {
+        if let Some(value) = value {
+            uri.push_str(&format!(
+                "&{}={}",
+                name,
+                RawStr::new(&value).percent_encode()
+            ));
+        }
+    }
+    let response = client.get(uri).dispatch();
+    let status = response.status();
+    let body: Value = response.into_json().ok_or("The response was not JSON")?;
+    println!("{}", serde_json::to_string_pretty(&body)?);
+    if status != Status::Ok {
+        return Err(format!("The query failed with {}", status).into());
+    }
+    Ok(())
+}
+
+fn match_file(figment: Figment, args: &cli::MatchArgs) -> Result<(), Box<dyn Error>> {
+    let settings = settings(&args.data.apply(figment))?;
+    let input = File::open(&args.input)
+        .map_err(|error| format!("Could not open {}: {}", args.input.display(), error))?;
+    let output = File::create(&args.output)
+        .map_err(|error| format!("Could not create {}: {}", args.output.display(), error))?;
+    let rejects_path = args.rejects();
+    let rejects = File::create(&rejects_path)
+        .map_err(|error| format!("Could not create {}: {}", rejects_path.display(), error))?;
+
+    let address_completer = AddressCompleter::init(&settings);
+    let start = Instant::now();
+    let summary = matching::match_csv(
+        &address_completer,
+        &args.column,
+        BufReader::new(input),
+        BufWriter::new(output),
+        BufWriter::new(rejects),
+        |summary| {
+            eprint!(
+                "\rMatched {} of {} rows ({:.0} rows/s)",
+                summary.matched,
+                summary.rows,
+                summary.rows as f64 / start.elapsed().as_secs_f64()
+            )
+        },
+    )?;
+    eprintln!();
+    eprintln!(
+        "{} rows matched into {}, {} rejected into {}",
+        summary.matched,
+        args.output.display(),
+        summary.rejected,
+        rejects_path.display()
+    );
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    let cli = cli::Cli::parse();
+    let figment = config::figment(cli.config.as_deref());
+
+    let filter = figment
+        .extract_inner::<String>("logging.filter")
+        .unwrap_or_else(|_| LoggingSettings::default().filter);
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or(filter)).init();
+
+    let result = match &cli.command {
+        None => serve(figment, &cli::ServeArgs::default()),
+        Some(cli::Command::Serve(args)) => serve(figment, args),
+        Some(cli::Command::Convert(args)) => convert(args),
+        Some(cli::Command::Snapshot(args)) => snapshot(figment, args),
+        Some(cli::Command::Query(args)) => query(figment, args),
+        Some(cli::Command::Match(args)) => match_file(figment, args),
+        Some(cli::Command::Update(args)) => update(figment, args),
+    };
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("{}", error);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::address_completer::fixture;
+
+    fn client() -> Client {
+        client_with(rocket::Config::figment())
+    }
+
+    fn client_with(figment: Figment) -> Client {
+        let settings: Settings = figment.extract().unwrap();
+        Client::tracked(rocket(figment, settings, |_| fixture())).unwrap()
+    }
+
+    #[test]
+    fn test_autocomplete_pages() {
+        let client = client();
+        let response = client
+            .get("/autocomplete?q=maribovej%201&side=2&per_side=2")
+            .dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        let body: Value = response.into_json().unwrap();
+        assert_eq!(body.as_array().unwrap().len(), 2);
+
+        let response = client
+            .get("/autocomplete?q=maribovej&side=2&per_side=1000")
+            .dispatch();
+        assert_eq!(response.status(), Status::BadRequest);
+        let body: Value = response.into_json().unwrap();
+        assert!(body["details"]["side"].is_string());
+    }
+
+    #[test]
+    fn test_static_backend() {
+        let client = client_with(
+            rocket::Config::figment()
+                .merge(("search.backend", "static"))
+                .merge(("href_base", "http://adresser.example.dk"))
+                .merge(("data.dir", "/nonexistent")),
+        );
+        let follow = |uri: &str| -> Value {
+            let body: Value = client.get(uri).dispatch().into_json().unwrap();
+            assert!(!body.as_array().unwrap().is_empty(), "{}", uri);
+            for suggestion in body.as_array().unwrap() {
+                let href = suggestion["data"]["href"].as_str().unwrap();
+                let path = href.trim_start_matches("http://adresser.example.dk");
+                assert_eq!(client.get(path).dispatch().status(), Status::Ok, "{}", href);
+            }
+            body
+        };
+        let streets = follow("/autocomplete?q=anything");
+        assert_eq!(streets[0]["tekst"], "Maribovej ");
+        let access_addresses = follow("/autocomplete?q=maribovej%201");
+        let access_address_id = access_addresses[1]["data"]["id"].as_str().unwrap();
+        let units = follow(&format!(
+            "/autocomplete?q=maribovej%2015&startfra=adresse&adgangsadresseid={}",
+            access_address_id
+        ));
+        assert_eq!(units.as_array().unwrap().len(), 3);
+
+        let body: Value = client
+            .get("/adgangsadresser/0a3f507a-c086-32b8-e044-0003ba298018")
+            .dispatch()
+            .into_json()
+            .unwrap();
+        assert_eq!(body["kommune"]["navn"], "København");
+
+        // No data to search or reload
+        assert_eq!(
+            client.get("/adresser").dispatch().status(),
+            Status::NotFound
+        );
+        let response = client.post("/admin/reload").dispatch();
+        assert_eq!(response.status(), Status::NotFound);
+    }
 }
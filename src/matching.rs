@@ -0,0 +1,201 @@
+use std::{
+    error::Error,
+    io::{Read, Write},
+    thread,
+};
+
+use csv::StringRecord;
+
+use crate::address_completer::AddressCompleter;
+use crate::washing::{Category, Washed};
+
+/// Rows read before they are matched in parallel and written
+const CHUNK_SIZE: usize = 4096;
+
+/// The columns appended to matched rows.
+pub const MATCH_COLUMNS: [&str; 6] = ["id", "adressebetegnelse", "x", "y", "kategori", "score"];
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Summary {
+    pub rows: usize,
+    pub matched: usize,
+    pub rejected: usize,
+}
+
+/// Matches the addresses in `column` of a CSV file, streaming it a chunk at
+/// a time. Rows matching with category A or B are written to `output` with
+/// the match appended, the others go to `rejects` with their category.
+/// `progress` is called after every chunk.
+pub fn match_csv<R: Read, W: Write, E: Write>(
+    completer: &AddressCompleter,
+    column: &str,
+    input: R,
+    output: W,
+    rejects: E,
+    mut progress: impl FnMut(&Summary),
+) -> Result<Summary, Box<dyn Error>> {
+    let mut reader = csv::Reader::from_reader(input);
+    let headers = reader.headers()?.clone();
+    let index = headers
+        .iter()
+        .position(|header| header == column)
+        .ok_or_else(|| {
+            format!(
+                "Column {} not found, the columns are {}",
+                column,
+                headers.iter().collect::<Vec<_>>().join(", ")
+            )
+        })?;
+
+    let mut output = csv::Writer::from_writer(output);
+    let mut rejects = csv::Writer::from_writer(rejects);
+    let mut output_headers = headers.clone();
+    output_headers.extend(MATCH_COLUMNS);
+    output.write_record(&output_headers)?;
+    let mut reject_headers = headers.clone();
+    reject_headers.push_field("kategori");
+    rejects.write_record(&reject_headers)?;
+
+    let mut summary = Summary::default();
+    let mut records = reader.records();
+    loop {
+        let chunk = records
+            .by_ref()
+            .take(CHUNK_SIZE)
+            .collect::<Result<Vec<StringRecord>, csv::Error>>()?;
+        if chunk.is_empty() {
+            break;
+        }
+        for (mut record, washed) in chunk
+            .iter()
+            .cloned()
+            .zip(wash_all(completer, &chunk, index))
+        {
+            summary.rows += 1;
+            match washed.candidates.first() {
+                Some(best) if washed.category != Category::C => {
+                    let address = &best.address;
+                    record.push_field(&address.id.to_string());
+                    record.push_field(&address.display_name());
+                    record.push_field(&coordinate(address.coordinates.map(|point| point.x)));
+                    record.push_field(&coordinate(address.coordinates.map(|point| point.y)));
+                    record.push_field(&washed.category.to_string());
+                    record.push_field(&format!("{:.3}", best.score));
+                    output.write_record(&record)?;
+                    summary.matched += 1;
+                }
+                best => {
+                    record.push_field(&best.map_or(String::new(), |_| washed.category.to_string()));
+                    rejects.write_record(&record)?;
+                    summary.rejected += 1;
+                }
+            }
+        }
+        progress(&summary);
+    }
+    output.flush()?;
+    rejects.flush()?;
+    Ok(summary)
+}
+
+fn coordinate(value: Option<f64>) -> String {
+    value.map(|value| value.to_string()).unwrap_or_default()
+}
+
+/// Washes the `index` column of every record, spread over the cores.
+fn wash_all(completer: &AddressCompleter, records: &[StringRecord], index: usize) -> Vec<Washed> {
+    let threads = thread::available_parallelism().map_or(1, |n| n.get());
+    let size = records.len().div_ceil(threads);
+    thread::scope(|scope| {
+        let handles: Vec<_> = records
+            .chunks(size)
+            .map(|records| {
+                scope.spawn(move || {
+                    records
+                        .iter()
+                        .map(|record| completer.wash(record.get(index).unwrap_or("")))
+                        .collect::<Vec<Washed>>()
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .flat_map(|handle| handle.join().unwrap())
+            .collect()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::address_completer::fixture;
+
+    #[test]
+    fn test_match_csv() {
+        let input = "kunde,adresse\n\
+            1,\"Maribovej 15, 1. tv, 2500 Valby\"\n\
+            2,Hovedgaden 1\n\
+            3,Ukendt Vej 7\n\
+            4,Mariboevej 10 4960 Holeby\n";
+        let mut output = Vec::new();
+        let mut rejects = Vec::new();
+        let mut reports = 0;
+        let summary = match_csv(
+            &fixture(),
+            "adresse",
+            input.as_bytes(),
+            &mut output,
+            &mut rejects,
+            |_| reports += 1,
+        )
+        .unwrap();
+        assert_eq!(
+            summary,
+            Summary {
+                rows: 4,
+                matched: 2,
+                rejected: 2
+            }
+        );
+        assert_eq!(reports, 1);
+
+        let output = String::from_utf8(output).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(
+            lines[0],
+            "kunde,adresse,id,adressebetegnelse,x,y,kategori,score"
+        );
+        assert!(lines[1].starts_with(
+            "1,\"Maribovej 15, 1. tv, 2500 Valby\",0a3f509f-96d9-32b8-e044-0003ba298018,"
+        ));
+        assert!(lines[1].ends_with(",12.48971377,55.667307,A,1.000"));
+        assert!(lines[2].starts_with("4,Mariboevej 10 4960 Holeby,"));
+
+        let rejects = String::from_utf8(rejects).unwrap();
+        assert_eq!(
+            rejects.lines().collect::<Vec<_>>(),
+            [
+                "kunde,adresse,kategori",
+                "2,Hovedgaden 1,C",
+                "3,Ukendt Vej 7,"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_missing_column() {
+        let error = match_csv(
+            &fixture(),
+            "adresse",
+            "kunde,betegnelse\n1,Maribovej 1\n".as_bytes(),
+            Vec::new(),
+            Vec::new(),
+            |_| {},
+        )
+        .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Column adresse not found, the columns are kunde, betegnelse"
+        );
+    }
+}
@@ -0,0 +1,150 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use uuid::Uuid;
+
+use crate::address::Address;
+
+/// A postnummer as derived from the addresses using it.
+#[derive(Debug)]
+pub struct PostalCode {
+    pub nr: String,
+    pub name: String,
+    /// Stormodtagerpostnummer, a postal code reserved for a single large
+    /// recipient
+    pub large_recipient: bool,
+    /// Address counts per kommunekode
+    pub municipal_codes: BTreeMap<i32, usize>,
+    /// Access addresses receiving mail on a large recipient postal code
+    pub large_recipient_addresses: BTreeSet<Uuid>,
+}
+
+impl PostalCode {
+    fn new(nr: &str, name: &str, large_recipient: bool) -> Self {
+        PostalCode {
+            nr: nr.to_string(),
+            name: name.to_string(),
+            large_recipient,
+            municipal_codes: BTreeMap::new(),
+            large_recipient_addresses: BTreeSet::new(),
+        }
+    }
+
+    pub fn addresses(&self) -> usize {
+        self.municipal_codes.values().sum()
+    }
+}
+
+pub struct PostalCodeIndex {
+    postal_codes: BTreeMap<String, PostalCode>,
+}
+
+impl PostalCodeIndex {
+    pub fn new() -> Self {
+        PostalCodeIndex {
+            postal_codes: BTreeMap::new(),
+        }
+    }
+
+    pub fn insert(&mut self, address: &Address) {
+        *self
+            .postal_codes
+            .entry(address.zip.clone())
+            .or_insert_with(|| PostalCode::new(&address.zip, &address.city, false))
+            .municipal_codes
+            .entry(address.municipal_code)
+            .or_default() += 1;
+
+        if !address.large_recipient_zip.is_empty() {
+            let postal_code = self
+                .postal_codes
+                .entry(address.large_recipient_zip.clone())
+                .or_insert_with(|| {
+                    PostalCode::new(
+                        &address.large_recipient_zip,
+                        &address.large_recipient_city,
+                        true,
+                    )
+                });
+            postal_code.large_recipient = true;
+            *postal_code
+                .municipal_codes
+                .entry(address.municipal_code)
+                .or_default() += 1;
+            postal_code
+                .large_recipient_addresses
+                .insert(address.access_address_id);
+        }
+    }
+
+    /// Takes back an `insert`, dropping postal codes without addresses.
+    /// `last_unit` tells whether no other address shares the access address.
+    pub fn remove(&mut self, address: &Address, last_unit: bool) {
+        let mut remove = |nr: &str, access_address: bool| {
+            let Some(postal_code) = self.postal_codes.get_mut(nr) else {
+                return;
+            };
+            if let Some(count) = postal_code.municipal_codes.get_mut(&address.municipal_code) {
+                *count -= 1;
+                if *count == 0 {
+                    postal_code.municipal_codes.remove(&address.municipal_code);
+                }
+            }
+            if access_address {
+                postal_code
+                    .large_recipient_addresses
+                    .remove(&address.access_address_id);
+            }
+            if postal_code.municipal_codes.is_empty() {
+                self.postal_codes.remove(nr);
+            }
+        };
+        remove(&address.zip, false);
+        if !address.large_recipient_zip.is_empty() {
+            remove(&address.large_recipient_zip, last_unit);
+        }
+    }
+
+    pub fn get(&self, nr: &str) -> Option<&PostalCode> {
+        self.postal_codes.get(nr)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &PostalCode> {
+        self.postal_codes.values()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_insert() {
+        let mut index = PostalCodeIndex::new();
+        let address = Address {
+            zip: "1218".to_string(),
+            city: "København K".to_string(),
+            municipal_code: 101,
+            ..Address::default()
+        };
+        index.insert(&address);
+        index.insert(&Address {
+            large_recipient_zip: "1240".to_string(),
+            large_recipient_city: "København K".to_string(),
+            ..address.clone()
+        });
+        index.insert(&Address {
+            municipal_code: 147,
+            ..address
+        });
+
+        let postal_code = index.get("1218").unwrap();
+        assert!(!postal_code.large_recipient);
+        assert_eq!(postal_code.addresses(), 3);
+        assert_eq!(postal_code.municipal_codes.len(), 2);
+
+        let postal_code = index.get("1240").unwrap();
+        assert!(postal_code.large_recipient);
+        assert_eq!(postal_code.addresses(), 1);
+        assert_eq!(postal_code.large_recipient_addresses.len(), 1);
+    }
+}
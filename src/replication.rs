@@ -0,0 +1,727 @@
+use std::{
+    collections::HashMap,
+    error::Error,
+    fs,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+
+use rocket::serde::{
+    de::{self, DeserializeOwned},
+    Deserialize, Deserializer, Serialize,
+};
+use uuid::Uuid;
+
+use crate::address::Address;
+use crate::address_completer::AddressCompleter;
+use crate::geo::Point;
+
+/// A change to one row of a DAWA entity, as the replication API
+/// (`/replikering/haendelser?entitet=...`) delivers them.
+#[derive(Debug, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct Event<T> {
+    pub sekvensnummer: u64,
+    pub operation: Operation,
+    pub data: T,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(crate = "rocket::serde", rename_all = "lowercase")]
+pub enum Operation {
+    Insert,
+    Update,
+    Delete,
+}
+
+/// A `vejstykke`, naming a street within a kommune.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct StreetData {
+    #[serde(deserialize_with = "code")]
+    pub kommunekode: i32,
+    #[serde(deserialize_with = "code")]
+    pub kode: i32,
+    pub navn: String,
+}
+
+/// A `postnummer`.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct PostalCodeData {
+    #[serde(deserialize_with = "code")]
+    pub nr: i32,
+    pub navn: String,
+}
+
+/// An `adgangsadresse`: a house number on a street with its postal code and
+/// position, in ETRS89 / UTM32.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct AccessAddressData {
+    pub id: Uuid,
+    pub status: Option<i32>,
+    #[serde(deserialize_with = "code")]
+    pub kommunekode: i32,
+    #[serde(deserialize_with = "code")]
+    pub vejkode: i32,
+    pub husnr: String,
+    pub supplerendebynavn: Option<String>,
+    #[serde(deserialize_with = "code")]
+    pub postnr: i32,
+    #[serde(rename = "etrs89koordinat_øst")]
+    pub east: Option<f64>,
+    #[serde(rename = "etrs89koordinat_nord")]
+    pub north: Option<f64>,
+}
+
+/// An `adresse`: floor and door of a unit, everything else coming from its
+/// access address.
+#[derive(Debug, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct AddressData {
+    pub id: Uuid,
+    pub status: Option<i32>,
+    pub adgangsadresseid: Uuid,
+    pub etage: Option<String>,
+    #[serde(rename = "dør")]
+    pub door: Option<String>,
+}
+
+impl From<&Address> for AccessAddressData {
+    fn from(unit: &Address) -> Self {
+        let position = unit.coordinates.map(Point::to_utm32);
+        AccessAddressData {
+            id: unit.access_address_id,
+            status: None,
+            kommunekode: unit.municipal_code,
+            vejkode: unit.street_code,
+            husnr: unit.number.clone(),
+            supplerendebynavn: Some(unit.placename.clone()).filter(|name| !name.is_empty()),
+            postnr: unit.zip.parse().unwrap_or_default(),
+            east: position.map(|position| position.x),
+            north: position.map(|position| position.y),
+        }
+    }
+}
+
+/// Codes come as numbers, or as strings with leading zeros.
+fn code<'de, D: Deserializer<'de>>(deserializer: D) -> Result<i32, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(crate = "rocket::serde", untagged)]
+    enum Code {
+        Number(i32),
+        Text(String),
+    }
+    match Code::deserialize(deserializer)? {
+        Code::Number(code) => Ok(code),
+        Code::Text(code) => code
+            .trim()
+            .parse()
+            .map_err(|_| de::Error::custom(format!("{} is not a code", code))),
+    }
+}
+
+/// Nedlagt (2) and henlagt (4) rows are gone as far as searching goes.
+fn is_live(status: Option<i32>) -> bool {
+    !matches!(status, Some(2 | 4))
+}
+
+/// An event of any of the entities an address is made of.
+#[derive(Debug)]
+pub enum Change {
+    Street(Operation, StreetData),
+    PostalCode(Operation, PostalCodeData),
+    AccessAddress(Operation, AccessAddressData),
+    Address(Operation, AddressData),
+}
+
+/// Where events are read from: the `haendelser` URL of the replication API,
+/// or a directory holding a saved response per entity, named after it, such
+/// as `adresse.json`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Source {
+    Http(String),
+    Directory(PathBuf),
+}
+
+impl From<&str> for Source {
+    fn from(source: &str) -> Self {
+        if source.starts_with("http://") || source.starts_with("https://") {
+            // The entities are asked for one by one
+            let url = source.split('?').next().unwrap_or(source);
+            Source::Http(url.trim_end_matches('/').to_string())
+        } else {
+            Source::Directory(PathBuf::from(source))
+        }
+    }
+}
+
+impl Source {
+    /// The changes after sequence number `after`, oldest first, with their
+    /// sequence numbers. Over HTTP every entity is read up to the latest
+    /// sequence number when starting, so none gets ahead of the others.
+    pub fn events(&self, after: u64) -> Result<Vec<(u64, Change)>, Box<dyn Error>> {
+        let until = match self {
+            Source::Http(url) => {
+                let latest = url
+                    .strip_suffix("haendelser")
+                    .ok_or("replication.source must be a /replikering/haendelser URL")?;
+                let latest: Latest = ureq::get(&format!("{}senestesekvensnummer", latest))
+                    .call()?
+                    .into_json()?;
+                Some(latest.sekvensnummer)
+            }
+            Source::Directory(_) => None,
+        };
+        let mut events = Vec::new();
+        for event in self.read("vejstykke", after, until)? {
+            events.push((
+                event.sekvensnummer,
+                Change::Street(event.operation, event.data),
+            ));
+        }
+        for event in self.read("postnummer", after, until)? {
+            let change = Change::PostalCode(event.operation, event.data);
+            events.push((event.sekvensnummer, change));
+        }
+        for event in self.read("adgangsadresse", after, until)? {
+            let change = Change::AccessAddress(event.operation, event.data);
+            events.push((event.sekvensnummer, change));
+        }
+        for event in self.read("adresse", after, until)? {
+            events.push((
+                event.sekvensnummer,
+                Change::Address(event.operation, event.data),
+            ));
+        }
+        events.retain(|(sequence, _)| *sequence > after);
+        events.sort_by_key(|(sequence, _)| *sequence);
+        Ok(events)
+    }
+
+    fn read<T: DeserializeOwned>(
+        &self,
+        entity: &str,
+        after: u64,
+        until: Option<u64>,
+    ) -> Result<Vec<Event<T>>, Box<dyn Error>> {
+        match self {
+            Source::Http(url) => {
+                let mut request = ureq::get(url)
+                    .query("entitet", entity)
+                    .query("sekvensnummerfra", &(after + 1).to_string());
+                if let Some(until) = until {
+                    request = request.query("sekvensnummertil", &until.to_string());
+                }
+                Ok(request.call()?.into_json()?)
+            }
+            Source::Directory(directory) => {
+                let path = directory.join(format!("{}.json", entity));
+                match fs::read_to_string(&path) {
+                    Ok(text) => Ok(serde_json::from_str(&text)
+                        .map_err(|error| format!("{}: {}", path.display(), error))?),
+                    Err(error) if error.kind() == ErrorKind::NotFound => Ok(Vec::new()),
+                    Err(error) => Err(error.into()),
+                }
+            }
+        }
+    }
+}
+
+/// `/replikering/senestesekvensnummer`
+#[derive(Deserialize)]
+#[serde(crate = "rocket::serde")]
+struct Latest {
+    sekvensnummer: u64,
+}
+
+/// What the events tell that the address file can't hold: access addresses
+/// without units and the names of streets and postal codes without
+/// addresses. It is kept between runs, as an `adresse` may come in a later
+/// run than its `adgangsadresse`.
+#[derive(Default)]
+pub struct Replica {
+    streets: HashMap<(i32, i32), String>,
+    postal_codes: HashMap<i32, String>,
+    access_addresses: HashMap<Uuid, AccessAddressData>,
+}
+
+/// A replica as saved, `replication.pending`.
+#[derive(Default, Deserialize, Serialize)]
+#[serde(crate = "rocket::serde", default)]
+struct Saved {
+    streets: Vec<StreetData>,
+    postal_codes: Vec<PostalCodeData>,
+    access_addresses: Vec<AccessAddressData>,
+}
+
+impl Replica {
+    /// The replica the last run saved, empty before the first.
+    pub fn read(path: &Path) -> Result<Replica, Box<dyn Error>> {
+        let saved: Saved = match fs::read_to_string(path) {
+            Ok(text) => serde_json::from_str(&text)
+                .map_err(|error| format!("{}: {}", path.display(), error))?,
+            Err(error) if error.kind() == ErrorKind::NotFound => Saved::default(),
+            Err(error) => return Err(error.into()),
+        };
+        Ok(Replica {
+            streets: saved
+                .streets
+                .into_iter()
+                .map(|street| ((street.kommunekode, street.kode), street.navn))
+                .collect(),
+            postal_codes: saved
+                .postal_codes
+                .into_iter()
+                .map(|postal_code| (postal_code.nr, postal_code.navn))
+                .collect(),
+            access_addresses: saved
+                .access_addresses
+                .into_iter()
+                .map(|access_address| (access_address.id, access_address))
+                .collect(),
+        })
+    }
+
+    /// Saves what the completer doesn't know of.
+    pub fn write(&self, path: &Path, completer: &AddressCompleter) -> Result<(), Box<dyn Error>> {
+        let saved = Saved {
+            streets: self
+                .streets
+                .iter()
+                .filter(|((municipal_code, street_code), _)| {
+                    completer
+                        .street_name(*municipal_code, *street_code)
+                        .is_none()
+                })
+                .map(|((municipal_code, street_code), name)| StreetData {
+                    kommunekode: *municipal_code,
+                    kode: *street_code,
+                    navn: name.clone(),
+                })
+                .collect(),
+            postal_codes: self
+                .postal_codes
+                .iter()
+                .filter(|(nr, _)| completer.postal_code(&format!("{:04}", nr)).is_none())
+                .map(|(nr, name)| PostalCodeData {
+                    nr: *nr,
+                    navn: name.clone(),
+                })
+                .collect(),
+            access_addresses: self.access_addresses.values().cloned().collect(),
+        };
+        let temporary = path.with_extension("tmp");
+        fs::write(&temporary, serde_json::to_string_pretty(&saved)?)?;
+        fs::rename(temporary, path)?;
+        Ok(())
+    }
+
+    /// Applies the changes in order, returning the sequence number of the
+    /// last.
+    pub fn apply(
+        &mut self,
+        completer: &mut AddressCompleter,
+        events: Vec<(u64, Change)>,
+    ) -> Result<Option<u64>, String> {
+        let mut last = None;
+        for (sequence, change) in events {
+            self.change(completer, change)
+                .map_err(|error| format!("sekvensnummer {}: {}", sequence, error))?;
+            last = Some(sequence);
+        }
+        Ok(last)
+    }
+
+    fn change(&mut self, completer: &mut AddressCompleter, change: Change) -> Result<(), String> {
+        match change {
+            // Streets and postal codes go with their last address
+            Change::Street(Operation::Delete, _) | Change::PostalCode(Operation::Delete, _) => {}
+            Change::Street(_, street) => {
+                if completer
+                    .street_name(street.kommunekode, street.kode)
+                    .is_some_and(|name| name != street.navn)
+                {
+                    completer.rename_street(street.kommunekode, street.kode, &street.navn)?;
+                }
+                self.streets
+                    .insert((street.kommunekode, street.kode), street.navn);
+            }
+            Change::PostalCode(_, postal_code) => {
+                self.postal_codes.insert(postal_code.nr, postal_code.navn);
+            }
+            Change::AccessAddress(operation, access_address) => {
+                let id = access_address.id;
+                if operation == Operation::Delete || !is_live(access_address.status) {
+                    for unit in completer.units(&id) {
+                        self.remove(completer, &unit.id);
+                    }
+                    self.access_addresses.remove(&id);
+                    return Ok(());
+                }
+                let template = self.template(completer, &access_address)?;
+                let units = completer.units(&id);
+                if units.is_empty() {
+                    // Until its first adresse
+                    self.access_addresses.insert(id, access_address);
+                    return Ok(());
+                }
+                for unit in units {
+                    completer.upsert_address(Address {
+                        id: unit.id,
+                        floor: unit.floor.clone(),
+                        door: unit.door.clone(),
+                        ..template.clone()
+                    })?;
+                }
+            }
+            Change::Address(operation, address) => {
+                if operation == Operation::Delete || !is_live(address.status) {
+                    self.remove(completer, &address.id);
+                    return Ok(());
+                }
+                let template = match self.access_addresses.get(&address.adgangsadresseid) {
+                    Some(access_address) => self.template(completer, access_address)?,
+                    None => completer
+                        .units(&address.adgangsadresseid)
+                        .first()
+                        .map(|unit| (**unit).clone())
+                        .ok_or_else(|| {
+                            format!(
+                                "adgangsadresse {} of adresse {} is unknown",
+                                address.adgangsadresseid, address.id
+                            )
+                        })?,
+                };
+                completer.upsert_address(Address {
+                    id: address.id,
+                    floor: address.etage.unwrap_or_default(),
+                    door: address.door.unwrap_or_default(),
+                    ..template
+                })?;
+                self.access_addresses.remove(&address.adgangsadresseid);
+            }
+        }
+        Ok(())
+    }
+
+    /// Removes a unit, keeping what only it told: the names of its street and
+    /// postal code, and its access address when it was the last unit.
+    fn remove(&mut self, completer: &mut AddressCompleter, id: &Uuid) {
+        let Some(unit) = completer.remove_address(id) else {
+            return;
+        };
+        self.streets
+            .entry((unit.municipal_code, unit.street_code))
+            .or_insert_with(|| unit.street.clone());
+        if let Ok(nr) = unit.zip.parse() {
+            self.postal_codes
+                .entry(nr)
+                .or_insert_with(|| unit.city.clone());
+        }
+        if completer.units(&unit.access_address_id).is_empty() {
+            self.access_addresses
+                .entry(unit.access_address_id)
+                .or_insert_with(|| AccessAddressData::from(&*unit));
+        }
+    }
+
+    /// The fields an access address gives its units.
+    fn template(
+        &self,
+        completer: &AddressCompleter,
+        access_address: &AccessAddressData,
+    ) -> Result<Address, String> {
+        let (municipal_code, street_code) = (access_address.kommunekode, access_address.vejkode);
+        let street = self
+            .streets
+            .get(&(municipal_code, street_code))
+            .map(String::as_str)
+            .or_else(|| completer.street_name(municipal_code, street_code))
+            .ok_or_else(|| {
+                format!(
+                    "vejstykke {} in kommune {} is unknown",
+                    street_code, municipal_code
+                )
+            })?;
+        let zip = format!("{:04}", access_address.postnr);
+        let city = self
+            .postal_codes
+            .get(&access_address.postnr)
+            .map(String::as_str)
+            .or_else(|| {
+                completer
+                    .postal_code(&zip)
+                    .map(|postal_code| postal_code.name.as_str())
+            })
+            .ok_or_else(|| format!("postnummer {} is unknown", zip))?;
+        // Large recipient postal codes are no part of the access address
+        let before = completer.access_address(&access_address.id);
+        Ok(Address {
+            id: Uuid::nil(),
+            street_code,
+            municipal_code,
+            street: street.to_string(),
+            number: access_address.husnr.clone(),
+            floor: String::new(),
+            door: String::new(),
+            zip,
+            placename: access_address.supplerendebynavn.clone().unwrap_or_default(),
+            city: city.to_string(),
+            coordinates: access_address
+                .east
+                .zip(access_address.north)
+                .map(|(east, north)| Point::from_utm32(Point::new(east, north))),
+            access_address_id: access_address.id,
+            large_recipient_zip: before
+                .as_ref()
+                .map(|before| before.large_recipient_zip.to_string())
+                .unwrap_or_default(),
+            large_recipient_city: before
+                .as_ref()
+                .map(|before| before.large_recipient_city.to_string())
+                .unwrap_or_default(),
+        })
+    }
+}
+
+/// The sequence number the address file is up to date with, `None` when it
+/// has never been updated.
+pub fn read_sequence(path: &Path) -> Result<Option<u64>, Box<dyn Error>> {
+    match fs::read_to_string(path) {
+        Ok(sequence) => Ok(Some(sequence.trim().parse()?)),
+        Err(error) if error.kind() == ErrorKind::NotFound => Ok(None),
+        Err(error) => Err(error.into()),
+    }
+}
+
+pub fn write_sequence(path: &Path, sequence: u64) -> std::io::Result<()> {
+    let temporary = path.with_extension("tmp");
+    fs::write(&temporary, format!("{}\n", sequence))?;
+    fs::rename(temporary, path)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{BufRead, BufReader, Write},
+        net::TcpListener,
+        thread,
+    };
+
+    use super::*;
+    use crate::address_completer::fixture;
+    use serde_json::Value;
+
+    fn events_directory() -> PathBuf {
+        PathBuf::from(concat!(env!("CARGO_MANIFEST_DIR"), "/fixtures/haendelser"))
+    }
+
+    fn assert_applied(completer: &AddressCompleter) {
+        // Inserted on a new street, joined with its new access address
+        let id = "0a3f50a0-0000-32b8-e044-0003ba298018".parse().unwrap();
+        let address = completer.address(&id).unwrap();
+        assert_eq!(address.street, "Testvej");
+        assert_eq!(address.city, "Holeby");
+        let point = address.coordinates.unwrap();
+        assert!((point.x - 11.4601).abs() < 1e-6 && (point.y - 54.7101).abs() < 1e-6);
+        assert!(completer.street("Testvej").is_some());
+        assert_eq!(
+            completer.find_street("testv".to_string(), 10, &Default::default()),
+            ["Testvej "]
+        );
+        let suggestions =
+            completer.find_access_address("testvej 7".to_string(), 1, &Default::default());
+        assert_eq!(suggestions[0].id, id);
+
+        // Updated, then the street renamed
+        let id = "0a3f509f-96d9-32b8-e044-0003ba298018".parse().unwrap();
+        let address = completer.address(&id).unwrap();
+        assert_eq!(address.door, "th");
+        assert_eq!(address.street, "Maribo Allé");
+        assert_eq!(completer.street_name(101, 4640), Some("Maribo Allé"));
+
+        // The house number of an access address changed for its unit
+        let id = "0a3f5090-3a1c-32b8-e044-0003ba298018".parse().unwrap();
+        assert_eq!(completer.address(&id).unwrap().number, "2A");
+
+        // Deleted, the last address on the street and in the postal code
+        let id = "0a3f5099-29f5-32b8-e044-0003ba298018".parse().unwrap();
+        assert!(completer.address(&id).is_none());
+        assert!(completer.street("Kronprinsesse Sofies Vej").is_none());
+        assert!(completer.postal_code("2000").is_none());
+        assert!(completer
+            .find_access_address("kronprinsesse".to_string(), 10, &Default::default())
+            .iter()
+            .all(|address| address.street != "Kronprinsesse Sofies Vej"));
+    }
+
+    #[test]
+    fn test_apply_from_directory() {
+        let mut completer = fixture();
+        let events = Source::from(events_directory().to_str().unwrap())
+            .events(0)
+            .unwrap();
+        assert_eq!(events.len(), 7);
+        assert_eq!(
+            Replica::default().apply(&mut completer, events),
+            Ok(Some(107))
+        );
+        assert_applied(&completer);
+
+        let events = Source::Directory(events_directory()).events(105).unwrap();
+        assert_eq!(events.len(), 2);
+        assert!(matches!(
+            events[0].1,
+            Change::AccessAddress(Operation::Update, _)
+        ));
+    }
+
+    #[test]
+    fn test_unknown_access_address() {
+        let mut completer = fixture();
+        let events: Vec<Event<AddressData>> = serde_json::from_str(
+            r#"[{"sekvensnummer": 1, "operation": "insert", "data": {
+                "id": "0a3f50a0-0000-32b8-e044-0003ba298018", "status": 1,
+                "adgangsadresseid": "0a3f50a0-0001-32b8-e044-0003ba298018",
+                "etage": null, "dør": null}}]"#,
+        )
+        .unwrap();
+        let events = events
+            .into_iter()
+            .map(|event| {
+                (
+                    event.sekvensnummer,
+                    Change::Address(event.operation, event.data),
+                )
+            })
+            .collect();
+        let error = Replica::default()
+            .apply(&mut completer, events)
+            .unwrap_err();
+        assert!(error.contains("adgangsadresse"), "{}", error);
+    }
+
+    #[test]
+    fn test_apply_from_http() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!(
+            "http://{}/replikering/haendelser?entitet=adresse",
+            listener.local_addr().unwrap()
+        );
+        let server = thread::spawn(move || {
+            let mut request_lines = Vec::new();
+            for _ in 0..5 {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut request_line = String::new();
+                BufReader::new(&stream)
+                    .read_line(&mut request_line)
+                    .unwrap();
+                let body = if request_line.contains("/senestesekvensnummer") {
+                    r#"{"sekvensnummer": 107, "tidspunkt": "2024-05-01T02:00:06.000Z"}"#.to_string()
+                } else {
+                    let entity = request_line.split(['=', '&']).nth(1).unwrap().to_string();
+                    fs::read_to_string(events_directory().join(format!("{}.json", entity)))
+                        .unwrap_or_else(|_| "[]".to_string())
+                };
+                write!(
+                    stream,
+                    "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    body.len(),
+                    body
+                )
+                .unwrap();
+                request_lines.push(request_line);
+            }
+            request_lines
+        });
+
+        let mut completer = fixture();
+        let events = Source::from(url.as_str()).events(100).unwrap();
+        let request_lines = server.join().unwrap();
+        assert!(request_lines[0].starts_with("GET /replikering/senestesekvensnummer "));
+        assert!(request_lines[4].starts_with(
+            "GET /replikering/haendelser?entitet=adresse&sekvensnummerfra=101&sekvensnummertil=107 "
+        ));
+        assert_eq!(
+            Replica::default().apply(&mut completer, events),
+            Ok(Some(107))
+        );
+        assert_applied(&completer);
+    }
+
+    #[test]
+    fn test_sequence_file() {
+        let path = std::env::temp_dir().join(format!("dawa-sekvensnummer-{}", std::process::id()));
+        assert_eq!(read_sequence(&path).unwrap(), None);
+        write_sequence(&path, 4242).unwrap();
+        assert_eq!(read_sequence(&path).unwrap(), Some(4242));
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_apply_across_runs() {
+        let path =
+            std::env::temp_dir().join(format!("dawa-replikering-{}.json", std::process::id()));
+        let mut completer = fixture();
+        let source = Source::Directory(events_directory());
+
+        // The vejstykke and adgangsadresse in one run, their adresse in the next
+        let (first, second): (Vec<_>, Vec<_>) = source
+            .events(0)
+            .unwrap()
+            .into_iter()
+            .partition(|(sequence, _)| *sequence <= 102);
+        let mut replica = Replica::read(&path).unwrap();
+        assert_eq!(replica.apply(&mut completer, first), Ok(Some(102)));
+        replica.write(&path, &completer).unwrap();
+
+        let error = Replica::default()
+            .apply(&mut fixture(), source.events(102).unwrap())
+            .unwrap_err();
+        assert!(error.contains("sekvensnummer 103"), "{}", error);
+
+        let mut replica = Replica::read(&path).unwrap();
+        assert_eq!(replica.apply(&mut completer, second), Ok(Some(107)));
+        assert_applied(&completer);
+        replica.write(&path, &completer).unwrap();
+
+        // Only what the address file can't hold is kept: the street and
+        // postal code of the deleted Kronprinsesse Sofies Vej 1
+        let saved: Value = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(saved["streets"].as_array().unwrap().len(), 1);
+        assert_eq!(saved["streets"][0]["navn"], "Kronprinsesse Sofies Vej");
+        assert_eq!(saved["postal_codes"][0]["nr"], 2000);
+        assert_eq!(
+            saved["access_addresses"][0]["id"],
+            "0a3f507b-4c12-32b8-e044-0003ba298018"
+        );
+
+        // A new unit on it finds the access address again
+        let events: Vec<Event<AddressData>> = serde_json::from_str(
+            r#"[{"sekvensnummer": 108, "operation": "insert", "data": {
+                "id": "0a3f5099-29f6-32b8-e044-0003ba298018", "status": 1,
+                "adgangsadresseid": "0a3f507b-4c12-32b8-e044-0003ba298018",
+                "etage": "1", "dør": null}}]"#,
+        )
+        .unwrap();
+        let events = events
+            .into_iter()
+            .map(|event| {
+                (
+                    event.sekvensnummer,
+                    Change::Address(event.operation, event.data),
+                )
+            })
+            .collect();
+        let mut replica = Replica::read(&path).unwrap();
+        assert_eq!(replica.apply(&mut completer, events), Ok(Some(108)));
+        let id = "0a3f5099-29f6-32b8-e044-0003ba298018".parse().unwrap();
+        assert_eq!(
+            completer.address(&id).unwrap().display_name(),
+            "Kronprinsesse Sofies Vej 1, 1., 2000 Frederiksberg"
+        );
+        fs::remove_file(path).unwrap();
+    }
+}
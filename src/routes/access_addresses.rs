@@ -0,0 +1,330 @@
+use rocket::{http::Status, serde::json::Value, State};
+use serde_json::{json, Map};
+
+use super::format::Output;
+use super::{
+    municipalities, nullable, parse_area, parse_parameter, parse_uuid, resource_not_found, Backend,
+    Completer, HrefBase, Page, Paged, Structure,
+};
+use crate::address_completer::{AccessAddressRef, AddressFilter};
+use crate::backends::SearchBackend;
+use crate::config::Settings;
+use crate::geo::Srid;
+
+#[derive(FromForm)]
+pub struct AccessAddressQuery {
+    q: Option<String>,
+    vejnavn: Option<String>,
+    husnr: Option<String>,
+    postnr: Option<String>,
+    kommunekode: Option<String>,
+    cirkel: Option<String>,
+    polygon: Option<String>,
+    bbox: Option<String>,
+    side: Option<String>,
+    per_side: Option<String>,
+    struktur: Option<String>,
+    format: Option<String>,
+    callback: Option<String>,
+    srid: Option<String>,
+}
+
+//GET https://api.dataforsyningen.dk/adgangsadresser?vejnavn=Maribovej&postnr=4960
+#[get("/adgangsadresser?<query..>")]
+pub fn search(
+    completer: Completer,
+    settings: &State<Settings>,
+    href: HrefBase,
+    query: AccessAddressQuery,
+) -> Paged {
+    let parameters = || -> Result<(AddressFilter, Structure, Page, Output), (Status, Value)> {
+        let filter = AddressFilter {
+            q: query.q.clone(),
+            street: query.vejnavn.clone(),
+            number: query.husnr.clone(),
+            zip: query.postnr.clone(),
+            municipal_code: parse_parameter("kommunekode", query.kommunekode.clone())?,
+            within: parse_area(
+                query.cirkel.clone(),
+                query.polygon.clone(),
+                query.bbox.clone(),
+                parse_parameter("srid", query.srid.clone())?.unwrap_or_default(),
+            )?,
+            ..AddressFilter::default()
+        };
+        let page = Page::parse(query.side.clone(), query.per_side.clone(), &settings.search)?;
+        let output = Output::parse(
+            query.format.clone(),
+            query.callback.clone(),
+            query.srid.clone(),
+        )?;
+        let structure = output
+            .format
+            .structure(parse_parameter("struktur", query.struktur.clone())?);
+        Ok((filter, structure, page, output))
+    };
+    let (filter, structure, page, Output { format, srid }) = match parameters() {
+        Ok(parameters) => parameters,
+        Err(error) => return error.into(),
+    };
+
+    let (result, total) = page.apply(
+        completer.search_access_addresses(&filter),
+        settings.search.total_count,
+    );
+    let results = result
+        .iter()
+        .map(|access_address| {
+            (
+                document(access_address, structure, srid, &*completer, &href.0),
+                access_address.coordinates.map(|point| srid.project(point)),
+            )
+        })
+        .collect();
+
+    Paged::new(format.render(results, srid), page, total)
+}
+
+//GET https://api.dataforsyningen.dk/adgangsadresser/0a3f507a-c086-32b8-e044-0003ba298018
+#[get("/adgangsadresser/<id>?<struktur>&<srid>")]
+pub fn get(
+    backend: Backend,
+    href: HrefBase,
+    id: &str,
+    struktur: Option<String>,
+    srid: Option<String>,
+) -> (Status, Value) {
+    let lookup = || -> Result<Value, (Status, Value)> {
+        let structure = parse_parameter("struktur", struktur)?.unwrap_or_default();
+        let srid = parse_parameter("srid", srid)?.unwrap_or_default();
+        let uuid = parse_uuid(id)?;
+        let access_address = backend
+            .access_address(&uuid)
+            .ok_or_else(|| resource_not_found(id))?;
+        Ok(document(
+            &access_address,
+            structure,
+            srid,
+            &*backend,
+            &href.0,
+        ))
+    };
+    match lookup() {
+        Ok(document) => (Status::Ok, document),
+        Err(error) => error,
+    }
+}
+
+pub fn document(
+    access_address: &AccessAddressRef,
+    structure: Structure,
+    srid: Srid,
+    backend: &dyn SearchBackend,
+    base: &str,
+) -> Value {
+    match structure {
+        Structure::Mini => mini(access_address, srid, base),
+        Structure::Nested => nested(access_address, srid, backend, base),
+        Structure::Flat => flat(access_address),
+    }
+}
+
+/// The fields of `mini` and `flad`, which differ only in how the coordinates
+/// are given. Addresses start from these too.
+pub fn fields(
+    access_address: &AccessAddressRef,
+    structure: Structure,
+    srid: Srid,
+) -> Map<String, Value> {
+    let Value::Object(mut fields) = json!({
+        "id": access_address.id,
+        "status": 1,
+        "darstatus": 3,
+        "vejkode": format!("{:04}", access_address.street_code),
+        "vejnavn": access_address.street,
+        "adresseringsvejnavn": access_address.street,
+        "husnr": access_address.number,
+        "supplerendebynavn": nullable(access_address.placename),
+        "postnr": access_address.zip,
+        "postnrnavn": access_address.city,
+        "stormodtagerpostnr": nullable(access_address.large_recipient_zip),
+        "stormodtagerpostnrnavn": nullable(access_address.large_recipient_city),
+        "kommunekode": format!("{:04}", access_address.municipal_code),
+    }) else {
+        unreachable!()
+    };
+    let coordinates = access_address.coordinates;
+    if structure == Structure::Mini {
+        let projected = coordinates.map(|point| srid.project(point));
+        fields.insert("x".into(), json!(projected.map(|point| point.x)));
+        fields.insert("y".into(), json!(projected.map(|point| point.y)));
+    } else {
+        let etrs89 = coordinates.map(|point| Srid::Etrs89.project(point));
+        fields.insert(
+            "wgs84koordinat_bredde".into(),
+            json!(coordinates.map(|point| point.y)),
+        );
+        fields.insert(
+            "wgs84koordinat_længde".into(),
+            json!(coordinates.map(|point| point.x)),
+        );
+        fields.insert(
+            "etrs89koordinat_øst".into(),
+            json!(etrs89.map(|point| point.x)),
+        );
+        fields.insert(
+            "etrs89koordinat_nord".into(),
+            json!(etrs89.map(|point| point.y)),
+        );
+    }
+    fields.insert("betegnelse".into(), json!(access_address.display_name()));
+    fields
+}
+
+pub fn mini(access_address: &AccessAddressRef, srid: Srid, base: &str) -> Value {
+    let mut fields = fields(access_address, Structure::Mini, srid);
+    fields.insert(
+        "href".into(),
+        json!(format!("{}/adgangsadresser/{}", base, access_address.id)),
+    );
+    Value::Object(fields)
+}
+
+fn flat(access_address: &AccessAddressRef) -> Value {
+    Value::Object(fields(access_address, Structure::Flat, Srid::default()))
+}
+
+pub fn nested(
+    access_address: &AccessAddressRef,
+    srid: Srid,
+    backend: &dyn SearchBackend,
+    base: &str,
+) -> Value {
+    json!({
+        "href": format!("{}/adgangsadresser/{}", base, access_address.id),
+        "id": access_address.id,
+        "status": 1,
+        "darstatus": 3,
+        "vejstykke": {
+            "href": format!(
+                "{}/vejstykker/{}/{}",
+                base, access_address.municipal_code, access_address.street_code
+            ),
+            "navn": access_address.street,
+            "adresseringsnavn": access_address.street,
+            "kode": format!("{:04}", access_address.street_code),
+        },
+        "husnr": access_address.number,
+        "supplerendebynavn": nullable(access_address.placename),
+        "postnummer": {
+            "href": format!("{}/postnumre/{}", base, access_address.zip),
+            "nr": access_address.zip,
+            "navn": access_address.city,
+        },
+        "stormodtagerpostnummer": if access_address.large_recipient_zip.is_empty() {
+            Value::Null
+        } else {
+            json!({
+                "href": format!("{}/postnumre/{}", base, access_address.large_recipient_zip),
+                "nr": access_address.large_recipient_zip,
+                "navn": access_address.large_recipient_city,
+            })
+        },
+        "kommune": municipalities::reference(backend, access_address.municipal_code, base),
+        "adgangspunkt": {
+            "koordinater": access_address
+                .coordinates
+                .map(|point| srid.project(point))
+                .map(|point| [point.x, point.y]),
+        },
+        "adressebetegnelse": access_address.display_name(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use rocket::http::{Header, Status};
+    use serde_json::Value;
+
+    use crate::routes::test::{client, client_with};
+
+    #[test]
+    fn test_search_and_follow_href() {
+        let figment = rocket::Config::figment().merge(("href_base", "http://adresser.example.dk/"));
+        let client = client_with(figment);
+        let body: Value = client
+            .get("/adgangsadresser?vejnavn=Hovedgaden&postnr=4520")
+            .dispatch()
+            .into_json()
+            .unwrap();
+        assert_eq!(body.as_array().unwrap().len(), 1);
+        let href = body[0]["href"].as_str().unwrap();
+        assert_eq!(
+            href,
+            "http://adresser.example.dk/adgangsadresser/0a3f5082-3f11-32b8-e044-0003ba298018"
+        );
+
+        let response = client
+            .get(href.trim_start_matches("http://adresser.example.dk"))
+            .dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        let body: Value = response.into_json().unwrap();
+        assert_eq!(body["adressebetegnelse"], "Hovedgaden 1, 4520 Svinninge");
+        assert_eq!(body["vejstykke"]["kode"], "1104");
+        assert_eq!(body["kommune"]["navn"], "Holbæk");
+        assert_eq!(body["adgangspunkt"]["koordinater"][1], 55.72061379);
+    }
+
+    #[test]
+    fn test_href_defaults_to_this_server() {
+        let client = client();
+        let body: Value = client
+            .get("/adgangsadresser?vejnavn=Hovedgaden&postnr=4520&struktur=mini")
+            .header(Header::new("Host", "evil.example"))
+            .dispatch()
+            .into_json()
+            .unwrap();
+        assert_eq!(
+            body[0]["href"],
+            "http://127.0.0.1:8000/adgangsadresser/0a3f5082-3f11-32b8-e044-0003ba298018"
+        );
+    }
+
+    #[test]
+    fn test_get_unknown_and_malformed_id() {
+        let client = client();
+        let response = client
+            .get("/adgangsadresser/00000000-0000-0000-0000-000000000001")
+            .dispatch();
+        assert_eq!(response.status(), Status::NotFound);
+        let body: Value = response.into_json().unwrap();
+        assert_eq!(body["type"], "ResourceNotFoundError");
+
+        let response = client.get("/adgangsadresser/not-a-uuid").dispatch();
+        assert_eq!(response.status(), Status::BadRequest);
+    }
+
+    #[test]
+    fn test_srid() {
+        let client = client();
+        let body: Value = client
+            .get("/adgangsadresser?bbox=719400,6174500,719500,6174600&srid=25832&struktur=mini")
+            .dispatch()
+            .into_json()
+            .unwrap();
+        assert_eq!(body.as_array().unwrap().len(), 1);
+        assert_eq!(body[0]["x"], 719462.43);
+        assert_eq!(body[0]["y"], 6174574.56);
+
+        let body: Value = client
+            .get("/adgangsadresser/0a3f507a-c086-32b8-e044-0003ba298018?struktur=flad")
+            .dispatch()
+            .into_json()
+            .unwrap();
+        assert_eq!(body["wgs84koordinat_længde"], 12.48971377);
+        assert!((body["etrs89koordinat_øst"].as_f64().unwrap() - 719462.43).abs() < 0.01);
+
+        let response = client.get("/adgangsadresser?srid=3857").dispatch();
+        assert_eq!(response.status(), Status::BadRequest);
+    }
+}
@@ -0,0 +1,438 @@
+use std::sync::Arc;
+
+use rocket::{
+    http::{ContentType, Status},
+    response::stream::TextStream,
+    serde::json::Value,
+    tokio::task,
+    State,
+};
+use serde_json::{json, Map};
+
+use super::format::Output;
+use super::{
+    access_addresses, nullable, parse_area, parse_parameter, parse_uuid, resource_not_found,
+    Backend, Completer, Door, HrefBase, Page, Paged, Structure,
+};
+use crate::address::Address;
+use crate::address_completer::{AccessAddressRef, AddressFilter};
+use crate::backends::SearchBackend;
+use crate::config::Settings;
+use crate::geo::Srid;
+
+/// Addresses looked at by each blocking task of an export
+const EXPORT_CHUNK: usize = 10_000;
+
+#[derive(FromForm)]
+pub struct AddressQuery {
+    q: Option<String>,
+    vejnavn: Option<String>,
+    husnr: Option<String>,
+    etage: Option<String>,
+    postnr: Option<String>,
+    kommunekode: Option<String>,
+    cirkel: Option<String>,
+    polygon: Option<String>,
+    bbox: Option<String>,
+    side: Option<String>,
+    per_side: Option<String>,
+    struktur: Option<String>,
+    format: Option<String>,
+    callback: Option<String>,
+    srid: Option<String>,
+}
+
+impl AddressQuery {
+    fn filter(&self, door: &Door) -> Result<AddressFilter, (Status, Value)> {
+        let srid = parse_parameter("srid", self.srid.clone())?.unwrap_or_default();
+        Ok(AddressFilter {
+            q: self.q.clone(),
+            street: self.vejnavn.clone(),
+            number: self.husnr.clone(),
+            floor: self.etage.clone(),
+            door: door.0.clone(),
+            zip: self.postnr.clone(),
+            municipal_code: parse_parameter("kommunekode", self.kommunekode.clone())?,
+            within: parse_area(
+                self.cirkel.clone(),
+                self.polygon.clone(),
+                self.bbox.clone(),
+                srid,
+            )?,
+        })
+    }
+}
+
+//GET https://api.dataforsyningen.dk/adresser?vejnavn=Maribovej&husnr=15&struktur=mini
+#[get("/adresser?<query..>")]
+pub fn search(
+    completer: Completer,
+    settings: &State<Settings>,
+    href: HrefBase,
+    door: Door,
+    query: AddressQuery,
+) -> Paged {
+    let parameters = || -> Result<(AddressFilter, Structure, Page, Output), (Status, Value)> {
+        let filter = query.filter(&door)?;
+        let page = Page::parse(query.side.clone(), query.per_side.clone(), &settings.search)?;
+        let output = Output::parse(
+            query.format.clone(),
+            query.callback.clone(),
+            query.srid.clone(),
+        )?;
+        let structure = output
+            .format
+            .structure(parse_parameter("struktur", query.struktur.clone())?);
+        Ok((filter, structure, page, output))
+    };
+    let (filter, structure, page, Output { format, srid }) = match parameters() {
+        Ok(parameters) => parameters,
+        Err(error) => return error.into(),
+    };
+
+    let (result, total) = page.apply(
+        completer.search_addresses(&filter),
+        settings.search.total_count,
+    );
+    let results = result
+        .into_iter()
+        .map(|address| {
+            (
+                document(address, structure, srid, &*completer, &href.0),
+                address.coordinates.map(|point| srid.project(point)),
+            )
+        })
+        .collect();
+
+    Paged::new(format.render(results, srid), page, total)
+}
+
+//GET /adresser?format=ndjson&kommunekode=0101
+/// Every address matching the filters, one flat document per line unless
+/// `struktur` says otherwise. Not paged, and written as the addresses are
+/// found rather than all at once.
+#[get("/adresser?format=ndjson&<query..>")]
+pub fn export(
+    completer: Completer,
+    href: HrefBase,
+    door: Door,
+    query: AddressQuery,
+) -> Result<(ContentType, TextStream![String]), (Status, Value)> {
+    let filter = Arc::new(query.filter(&door)?);
+    let structure = parse_parameter("struktur", query.struktur.clone())?.unwrap_or(Structure::Flat);
+    let srid: Srid = parse_parameter("srid", query.srid.clone())?.unwrap_or_default();
+    let completer = completer.0;
+    let base = href.0;
+    let stream = TextStream! {
+        let mut after = None;
+        loop {
+            let (completer, filter, base) = (completer.clone(), filter.clone(), base.clone());
+            let chunk = task::spawn_blocking(move || {
+                let (addresses, last) = completer.scan_addresses(&filter, after, EXPORT_CHUNK);
+                let lines: String = addresses
+                    .iter()
+                    .map(|address| {
+                        format!("{}\n", document(address, structure, srid, &*completer, &base))
+                    })
+                    .collect();
+                (lines, last)
+            })
+            .await;
+            match chunk {
+                Ok((lines, last)) => {
+                    if !lines.is_empty() {
+                        yield lines;
+                    }
+                    match last {
+                        Some(last) => after = Some(last),
+                        None => break,
+                    }
+                }
+                Err(error) => {
+                    // A last line that is no address, so a cut-short export
+                    // can't pass for a whole one
+                    error!("Exporting addresses failed: {}", error);
+                    let error = json!({
+                        "type": "InternalServerError",
+                        "title": "Eksporten blev afbrudt",
+                        "details": error.to_string(),
+                    });
+                    yield format!("{}\n", error);
+                    break;
+                }
+            }
+        }
+    };
+    Ok((ContentType::new("application", "x-ndjson"), stream))
+}
+
+//GET https://api.dataforsyningen.dk/adresser/0a3f509f-96d7-32b8-e044-0003ba298018
+#[get("/adresser/<id>?<struktur>&<srid>")]
+pub fn get(
+    backend: Backend,
+    href: HrefBase,
+    id: &str,
+    struktur: Option<String>,
+    srid: Option<String>,
+) -> (Status, Value) {
+    let lookup = || -> Result<Value, (Status, Value)> {
+        let structure = parse_parameter("struktur", struktur)?.unwrap_or_default();
+        let srid = parse_parameter("srid", srid)?.unwrap_or_default();
+        let uuid = parse_uuid(id)?;
+        let address = backend
+            .address(&uuid)
+            .ok_or_else(|| resource_not_found(id))?;
+        Ok(document(&address, structure, srid, &*backend, &href.0))
+    };
+    match lookup() {
+        Ok(document) => (Status::Ok, document),
+        Err(error) => error,
+    }
+}
+
+pub fn document(
+    address: &Address,
+    structure: Structure,
+    srid: Srid,
+    backend: &dyn SearchBackend,
+    base: &str,
+) -> Value {
+    match structure {
+        Structure::Mini => mini(address, srid, base),
+        Structure::Nested => nested(address, srid, backend, base),
+        Structure::Flat => flat(address),
+    }
+}
+
+/// The access address fields with the address's own id, unit and name.
+fn fields(address: &Address, structure: Structure, srid: Srid) -> Map<String, Value> {
+    let mut fields = access_addresses::fields(&AccessAddressRef::from(address), structure, srid);
+    fields.insert("id".into(), json!(address.id));
+    fields.insert("husnr".into(), json!(address.number));
+    fields.insert("betegnelse".into(), json!(address.display_name()));
+    let unit = [
+        ("husnr", "etage", nullable(&address.floor)),
+        ("etage", "dør", nullable(&address.door)),
+        (
+            "kommunekode",
+            "adgangsadresseid",
+            json!(address.access_address_id),
+        ),
+    ];
+    for (after, key, value) in unit {
+        let index = fields.keys().position(|field| field == after).unwrap() + 1;
+        fields.shift_insert(index, key.into(), value);
+    }
+    fields
+}
+
+pub fn mini(address: &Address, srid: Srid, base: &str) -> Value {
+    let mut fields = fields(address, Structure::Mini, srid);
+    fields.insert(
+        "href".into(),
+        json!(format!("{}/adresser/{}", base, address.id)),
+    );
+    Value::Object(fields)
+}
+
+fn flat(address: &Address) -> Value {
+    Value::Object(fields(address, Structure::Flat, Srid::default()))
+}
+
+fn nested(address: &Address, srid: Srid, backend: &dyn SearchBackend, base: &str) -> Value {
+    json!({
+        "id": address.id,
+        "status": 1,
+        "darstatus": 3,
+        "href": format!("{}/adresser/{}", base, address.id),
+        "etage": nullable(&address.floor),
+        "dør": nullable(&address.door),
+        "adressebetegnelse": address.display_name(),
+        "adgangsadresse": access_addresses::nested(&AccessAddressRef::from(address), srid, backend, base),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use rocket::http::{ContentType, Status};
+    use serde_json::Value;
+
+    use crate::routes::test::{client, client_with};
+
+    #[test]
+    fn test_search_structures() {
+        let client = client();
+        let response = client
+            .get("/adresser?vejnavn=Maribovej&husnr=15&etage=st&struktur=mini")
+            .dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        let body: Value = response.into_json().unwrap();
+        assert_eq!(body.as_array().unwrap().len(), 2);
+
+        let response = client
+            .get("/adresser?vejnavn=Maribovej&husnr=15&etage=st&d%C3%B8r=th&struktur=mini")
+            .dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        let body: Value = response.into_json().unwrap();
+        assert_eq!(body.as_array().unwrap().len(), 1);
+        assert_eq!(body[0]["dør"], "th");
+        assert_eq!(body[0]["vejkode"], "4640");
+        assert_eq!(body[0]["kommunekode"], "0101");
+        assert_eq!(body[0]["x"], 12.48971377);
+
+        let body: Value = client
+            .get("/adresser?q=maribovej%2015%201%20tv")
+            .dispatch()
+            .into_json()
+            .unwrap();
+        assert_eq!(body[0]["etage"], "1");
+        assert_eq!(body[0]["adgangsadresse"]["postnummer"]["nr"], "2500");
+
+        let body: Value = client
+            .get("/adresser?kommunekode=0360&struktur=flad&side=2&per_side=3")
+            .dispatch()
+            .into_json()
+            .unwrap();
+        assert_eq!(body.as_array().unwrap().len(), 1);
+        assert_eq!(body[0]["etage"], Value::Null);
+        let fields: Vec<&str> = body[0]
+            .as_object()
+            .unwrap()
+            .keys()
+            .map(|key| key.as_str())
+            .collect();
+        assert_eq!(fields[6..9], ["husnr", "etage", "dør"]);
+        assert_eq!(fields[14..16], ["kommunekode", "adgangsadresseid"]);
+        assert_eq!(fields.last(), Some(&"betegnelse"));
+    }
+
+    #[test]
+    fn test_get() {
+        let client = client();
+        let response = client
+            .get("/adresser/0a3f509f-96d8-32b8-e044-0003ba298018")
+            .dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        let body: Value = response.into_json().unwrap();
+        assert_eq!(body["dør"], "th");
+        assert_eq!(
+            body["adgangsadresse"]["id"],
+            "0a3f507a-c086-32b8-e044-0003ba298018"
+        );
+
+        let response = client
+            .get("/adresser/0a3f507a-c086-32b8-e044-0003ba298018")
+            .dispatch();
+        assert_eq!(response.status(), Status::NotFound);
+        let body: Value = response.into_json().unwrap();
+        assert_eq!(body["type"], "ResourceNotFoundError");
+        assert_eq!(
+            body["details"]["id"],
+            "0a3f507a-c086-32b8-e044-0003ba298018"
+        );
+
+        let response = client.get("/adresser/0a3f509f").dispatch();
+        assert_eq!(response.status(), Status::BadRequest);
+        let body: Value = response.into_json().unwrap();
+        assert_eq!(body["type"], "ResourcePathFormatError");
+    }
+
+    #[test]
+    fn test_search_invalid_parameters() {
+        let client = client();
+        let response = client.get("/adresser?struktur=stor").dispatch();
+        assert_eq!(response.status(), Status::BadRequest);
+        let response = client.get("/adresser?kommunekode=abc").dispatch();
+        assert_eq!(response.status(), Status::BadRequest);
+        let body: Value = response.into_json().unwrap();
+        assert_eq!(body["type"], "QueryParameterFormatError");
+
+        for query in [
+            "bbox=-1e300,-1e300,1e300,1e300",
+            "cirkel=12,55,inf",
+            "cirkel=1e12,1e12,10&srid=25832",
+        ] {
+            let response = client.get(format!("/adresser?{}", query)).dispatch();
+            assert_eq!(response.status(), Status::BadRequest, "{}", query);
+        }
+    }
+
+    #[test]
+    fn test_pagination() {
+        let client = client();
+        for query in [
+            "side=0",
+            "side=-1",
+            "per_side=0",
+            "per_side=-5",
+            "per_side=1001",
+        ] {
+            let response = client
+                .get(format!("/adresser?kommunekode=0360&{}", query))
+                .dispatch();
+            assert_eq!(response.status(), Status::BadRequest, "{}", query);
+            let body: Value = response.into_json().unwrap();
+            assert_eq!(body["type"], "QueryParameterFormatError");
+        }
+        let response = client.get("/adresser?side=2&per_side=3").dispatch();
+        assert_eq!(response.headers().get_one("X-Total-Count"), None);
+
+        let figment = rocket::Config::figment().merge(("search.total_count", true));
+        let client = client_with(figment);
+        let response = client
+            .get("/adresser?kommunekode=0360&side=2&per_side=3&struktur=mini")
+            .dispatch();
+        assert_eq!(response.headers().get_one("X-Total-Count"), Some("4"));
+        assert_eq!(
+            response.headers().get_one("Link"),
+            Some(
+                "<http://127.0.0.1:8000/adresser?kommunekode=0360&per_side=3&struktur=mini&side=1>; rel=\"first\", \
+                 <http://127.0.0.1:8000/adresser?kommunekode=0360&per_side=3&struktur=mini&side=1>; rel=\"prev\", \
+                 <http://127.0.0.1:8000/adresser?kommunekode=0360&per_side=3&struktur=mini&side=2>; rel=\"last\""
+            )
+        );
+        let body: Value = response.into_json().unwrap();
+        assert_eq!(body.as_array().unwrap().len(), 1);
+
+        let response = client.get("/kommuner?side=5").dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(
+            response.into_json::<Value>().unwrap(),
+            Value::Array(Vec::new())
+        );
+    }
+
+    #[test]
+    fn test_export_ndjson() {
+        let client = client();
+        let response = client.get("/adresser?format=ndjson").dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(
+            response.content_type(),
+            Some(ContentType::new("application", "x-ndjson"))
+        );
+        let text = response.into_string().unwrap();
+        let lines: Vec<Value> = text
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 15);
+        assert!(lines[0]["wgs84koordinat_bredde"].is_number());
+
+        let text = client
+            .get("/adresser?format=ndjson&postnr=2500&etage=st&struktur=mini")
+            .dispatch()
+            .into_string()
+            .unwrap();
+        let doors: Vec<String> = text
+            .lines()
+            .map(|line| serde_json::from_str::<Value>(line).unwrap()["dør"].to_string())
+            .collect();
+        assert_eq!(doors, ["\"tv\"", "\"th\""]);
+
+        let response = client
+            .get("/adresser?format=ndjson&kommunekode=x")
+            .dispatch();
+        assert_eq!(response.status(), Status::BadRequest);
+    }
+}
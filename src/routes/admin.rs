@@ -0,0 +1,117 @@
+use std::sync::Arc;
+
+use rocket::{
+    http::Status,
+    request::{FromRequest, Outcome},
+    serde::json::Value,
+    Request, State,
+};
+use serde_json::json;
+
+use crate::config::Settings;
+use crate::dataset::{Dataset, ReloadError};
+
+/// The token from an `Authorization: Bearer` header.
+pub struct Bearer(Option<String>);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Bearer {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, ()> {
+        let token = request
+            .headers()
+            .get_one("Authorization")
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .map(|token| token.trim().to_string());
+        Outcome::Success(Bearer(token))
+    }
+}
+
+/// Compares every byte whatever the first difference, so the time taken
+/// doesn't tell how much of a guessed token is right.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len()
+        && a.iter()
+            .zip(b)
+            .fold(0, |difference, (a, b)| difference | (a ^ b))
+            == 0
+}
+
+//POST /admin/reload with Authorization: Bearer <reload.token>
+#[post("/admin/reload")]
+pub async fn reload(
+    dataset: &State<Arc<Dataset>>,
+    settings: &State<Settings>,
+    bearer: Bearer,
+) -> (Status, Value) {
+    let authorized = match (&settings.reload.token, &bearer.0) {
+        (Some(token), Some(given)) => constant_time_eq(token.as_bytes(), given.as_bytes()),
+        _ => false,
+    };
+    if !authorized {
+        return (
+            Status::Forbidden,
+            json!({
+                "type": "NotAuthorizedError",
+                "title": "Reload requires the configured token",
+            }),
+        );
+    }
+    match dataset.inner().clone().reload().await {
+        Ok(count) => (Status::Ok, json!({ "adresser": count })),
+        Err(ReloadError::Busy) => (
+            Status::Conflict,
+            json!({
+                "type": "ReloadInProgressError",
+                "title": "A reload is already running",
+            }),
+        ),
+        Err(ReloadError::Failed(details)) => (
+            Status::InternalServerError,
+            json!({
+                "type": "ReloadError",
+                "title": "The addresses could not be loaded, the old ones are still served",
+                "details": details,
+            }),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rocket::http::{Header, Status};
+    use serde_json::Value;
+
+    use crate::routes::test::{client, client_with};
+
+    #[test]
+    fn test_reload_requires_token() {
+        let client = client();
+        let response = client.post("/admin/reload").dispatch();
+        assert_eq!(response.status(), Status::Forbidden);
+
+        let figment = rocket::Config::figment()
+            .merge(("reload.token", "hemmelig"))
+            .merge(("data.dir", concat!(env!("CARGO_MANIFEST_DIR"), "/fixtures")));
+        let client = client_with(figment);
+        for wrong in ["forkert", "hemmelit", "hemmelig2"] {
+            let response = client
+                .post("/admin/reload")
+                .header(Header::new("Authorization", format!("Bearer {}", wrong)))
+                .dispatch();
+            assert_eq!(response.status(), Status::Forbidden);
+        }
+
+        let response = client
+            .post("/admin/reload")
+            .header(Header::new("Authorization", "Bearer hemmelig"))
+            .dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        let body: Value = response.into_json().unwrap();
+        assert!(body["adresser"].as_u64().unwrap() > 0);
+
+        let response = client.get("/postnumre/2500").dispatch();
+        assert_eq!(response.status(), Status::Ok);
+    }
+}
@@ -0,0 +1,262 @@
+use rocket::{
+    http::{ContentType, Status},
+    response::{self, Responder},
+    serde::json::Value,
+    Request,
+};
+use serde_json::json;
+
+use super::{parse_parameter, query_parameter_error, Structure};
+use crate::geo::{Point, Srid};
+
+/// The `format` parameter. `callback` alone also asks for JSONP, as in DAWA.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub enum Format {
+    #[default]
+    Json,
+    GeoJson,
+    Csv,
+    /// Calling the function named by `callback`
+    Jsonp(String),
+}
+
+impl Format {
+    pub fn parse(
+        format: Option<String>,
+        callback: Option<String>,
+    ) -> Result<Format, (Status, Value)> {
+        let format = match (format.as_deref(), callback) {
+            (None | Some("json"), None) => Format::Json,
+            (None | Some("json" | "jsonp"), Some(callback)) => Format::Jsonp(callback),
+            (Some("jsonp"), None) => {
+                return Err(query_parameter_error(
+                    "callback",
+                    "callback skal angives med format=jsonp".to_string(),
+                ))
+            }
+            (Some("geojson"), _) => Format::GeoJson,
+            (Some("csv"), _) => Format::Csv,
+            (Some(format), _) => {
+                return Err(query_parameter_error(
+                    "format",
+                    format!("{} er ikke et gyldigt format", format),
+                ))
+            }
+        };
+        if let Format::Jsonp(callback) = &format {
+            // Anything else could inject script into the page loading it
+            if callback.is_empty()
+                || !callback
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '$' | '.'))
+            {
+                return Err(query_parameter_error(
+                    "callback",
+                    format!("{} er ikke et gyldigt funktionsnavn", callback),
+                ));
+            }
+        }
+        Ok(format)
+    }
+
+    /// The `struktur` to use: CSV is always flat and GeoJSON flat unless
+    /// asked otherwise.
+    pub fn structure(&self, structure: Option<Structure>) -> Structure {
+        match self {
+            Format::Csv => Structure::Flat,
+            Format::GeoJson => structure.unwrap_or(Structure::Flat),
+            Format::Json | Format::Jsonp(_) => structure.unwrap_or_default(),
+        }
+    }
+
+    /// Search results as a response, each given with its position in `srid`
+    /// for GeoJSON.
+    pub fn render(&self, results: Vec<(Value, Option<Point>)>, srid: Srid) -> Formatted {
+        match self {
+            Format::Json => Formatted::Json(Status::Ok, json!(strip(results))),
+            Format::Jsonp(callback) => Formatted::Jsonp(callback.clone(), json!(strip(results))),
+            Format::GeoJson => Formatted::GeoJson(feature_collection(results, srid)),
+            Format::Csv => match csv(strip(results)) {
+                Ok(text) => Formatted::Csv(text),
+                Err(error) => Formatted::Json(
+                    Status::InternalServerError,
+                    json!({ "type": "InternalServerError", "title": error.to_string() }),
+                ),
+            },
+        }
+    }
+}
+
+fn strip(results: Vec<(Value, Option<Point>)>) -> Vec<Value> {
+    results.into_iter().map(|(document, _)| document).collect()
+}
+
+fn feature_collection(results: Vec<(Value, Option<Point>)>, srid: Srid) -> Value {
+    let features: Vec<Value> = results
+        .into_iter()
+        .map(|(properties, point)| {
+            json!({
+                "type": "Feature",
+                "geometry": point.map(|point| json!({
+                    "type": "Point",
+                    "coordinates": [point.x, point.y],
+                })),
+                "properties": properties,
+            })
+        })
+        .collect();
+    json!({
+        "type": "FeatureCollection",
+        "crs": {
+            "type": "name",
+            "properties": { "name": format!("EPSG:{}", srid.code()) },
+        },
+        "features": features,
+    })
+}
+
+/// A header row with the fields of the first document, then a row per
+/// document. Nested values are written as JSON.
+fn csv(documents: Vec<Value>) -> Result<String, csv::Error> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    if let Some(Value::Object(first)) = documents.first() {
+        writer.write_record(first.keys())?;
+    }
+    for document in &documents {
+        let Value::Object(fields) = document else {
+            continue;
+        };
+        writer.write_record(fields.values().map(|value| match value {
+            Value::Null => String::new(),
+            Value::String(text) => text.clone(),
+            value => value.to_string(),
+        }))?;
+    }
+    let bytes = writer.into_inner().map_err(|error| error.into_error())?;
+    Ok(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+/// The `format`, `callback` and `srid` parameters of a search.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Output {
+    pub format: Format,
+    pub srid: Srid,
+}
+
+impl Output {
+    pub fn parse(
+        format: Option<String>,
+        callback: Option<String>,
+        srid: Option<String>,
+    ) -> Result<Output, (Status, Value)> {
+        Ok(Output {
+            format: Format::parse(format, callback)?,
+            srid: parse_parameter("srid", srid)?.unwrap_or_default(),
+        })
+    }
+}
+
+/// A response in one of the formats.
+pub enum Formatted {
+    Json(Status, Value),
+    GeoJson(Value),
+    Csv(String),
+    Jsonp(String, Value),
+}
+
+impl From<(Status, Value)> for Formatted {
+    fn from((status, body): (Status, Value)) -> Self {
+        Formatted::Json(status, body)
+    }
+}
+
+impl From<Value> for Formatted {
+    fn from(body: Value) -> Self {
+        Formatted::Json(Status::Ok, body)
+    }
+}
+
+impl<'r> Responder<'r, 'static> for Formatted {
+    fn respond_to(self, request: &'r Request<'_>) -> response::Result<'static> {
+        match self {
+            Formatted::Json(status, body) => (status, body).respond_to(request),
+            Formatted::GeoJson(body) => (
+                ContentType::new("application", "geo+json"),
+                body.to_string(),
+            )
+                .respond_to(request),
+            Formatted::Csv(text) => (ContentType::CSV, text).respond_to(request),
+            Formatted::Jsonp(callback, body) => {
+                (ContentType::JavaScript, format!("{}({});", callback, body)).respond_to(request)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rocket::http::{ContentType, Status};
+    use serde_json::Value;
+
+    use super::*;
+    use crate::routes::test::client;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(Format::parse(None, None), Ok(Format::Json));
+        assert_eq!(
+            Format::parse(None, Some("cb".to_string())),
+            Ok(Format::Jsonp("cb".to_string()))
+        );
+        assert_eq!(
+            Format::parse(Some("csv".to_string()), None),
+            Ok(Format::Csv)
+        );
+        assert!(Format::parse(Some("jsonp".to_string()), None).is_err());
+        assert!(Format::parse(Some("xml".to_string()), None).is_err());
+        assert!(Format::parse(None, Some("alert(1)//".to_string())).is_err());
+    }
+
+    #[test]
+    fn test_formats() {
+        let client = client();
+        let response = client
+            .get("/adresser?vejnavn=Maribovej&husnr=15&format=geojson&srid=25832")
+            .dispatch();
+        assert_eq!(
+            response.content_type(),
+            Some(ContentType::new("application", "geo+json"))
+        );
+        let body: Value = serde_json::from_str(&response.into_string().unwrap()).unwrap();
+        assert_eq!(body["type"], "FeatureCollection");
+        assert_eq!(body["crs"]["properties"]["name"], "EPSG:25832");
+        assert_eq!(body["features"].as_array().unwrap().len(), 3);
+        let coordinates = &body["features"][0]["geometry"]["coordinates"];
+        assert_eq!(coordinates[0].as_f64().unwrap().round(), 719462.0);
+        assert_eq!(body["features"][0]["properties"]["etage"], "st");
+
+        let response = client
+            .get("/adgangsadresser?vejnavn=Maribovej&postnr=4960&format=csv")
+            .dispatch();
+        assert_eq!(response.content_type(), Some(ContentType::CSV));
+        let text = response.into_string().unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines.len(), 5);
+        assert!(lines[0].starts_with("id,status,darstatus,vejkode,vejnavn"));
+        assert!(lines[1].contains(",Maribovej,1,"));
+
+        let response = client
+            .get("/adresser?vejnavn=Maribovej&husnr=15&callback=vis")
+            .dispatch();
+        assert_eq!(response.content_type(), Some(ContentType::JavaScript));
+        let text = response.into_string().unwrap();
+        assert!(
+            text.starts_with("vis([{") && text.ends_with("]);"),
+            "{}",
+            text
+        );
+
+        let response = client.get("/adresser?format=xml").dispatch();
+        assert_eq!(response.status(), Status::BadRequest);
+    }
+}
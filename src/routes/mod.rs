@@ -0,0 +1,434 @@
+use std::{ops::Deref, str::FromStr, sync::Arc};
+
+use rocket::{
+    http::Status,
+    request::{FromRequest, Outcome},
+    response::{self, Responder},
+    serde::json::Value,
+    Request, Route,
+};
+use serde_json::json;
+use uuid::Uuid;
+
+use crate::address_completer::AddressCompleter;
+use crate::backends::{r#static::StaticBackend, SearchBackend};
+use crate::config::{BackendKind, SearchSettings, Settings};
+use crate::dataset::Dataset;
+use crate::geo::{Area, Srid};
+use format::Formatted;
+
+pub mod access_addresses;
+pub mod addresses;
+pub mod admin;
+pub mod format;
+pub mod municipalities;
+pub mod postal_codes;
+pub mod streets;
+pub mod washing;
+
+pub fn routes() -> Vec<Route> {
+    let mut routes = routes![
+        addresses::search,
+        addresses::export,
+        access_addresses::search,
+        streets::search,
+        streets::autocomplete,
+        postal_codes::search,
+        postal_codes::autocomplete,
+        postal_codes::get,
+        municipalities::search,
+        municipalities::autocomplete,
+        municipalities::get,
+        washing::wash,
+        washing::wash_batch,
+        admin::reload
+    ];
+    routes.extend(lookup_routes());
+    routes
+}
+
+/// The routes `/autocomplete` suggestions link to, answered by the
+/// `search.backend` and so also served by the static one.
+pub fn lookup_routes() -> Vec<Route> {
+    routes![addresses::get, access_addresses::get, streets::get]
+}
+
+/// Where the `href` links in responses point: the configured `href_base`, or
+/// the address and port this server listens on. The `Host` header is never
+/// trusted, as responses may be cached for other clients.
+pub struct HrefBase(pub String);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for HrefBase {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, ()> {
+        Outcome::Success(HrefBase(href_base(request)))
+    }
+}
+
+fn href_base(request: &Request<'_>) -> String {
+    let configured = request
+        .rocket()
+        .state::<Settings>()
+        .and_then(|settings| settings.href_base.clone());
+    let base = configured.unwrap_or_else(|| {
+        let config = request.rocket().config();
+        format!("http://{}:{}", config.address, config.port)
+    });
+    base.trim_end_matches('/').to_string()
+}
+
+/// The addresses as they were when the request came in. A reload replaces
+/// them for later requests only.
+pub struct Completer(pub Arc<AddressCompleter>);
+
+impl Deref for Completer {
+    type Target = AddressCompleter;
+
+    fn deref(&self) -> &AddressCompleter {
+        &self.0
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Completer {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, ()> {
+        match request.rocket().state::<Arc<Dataset>>() {
+            Some(dataset) => Outcome::Success(Completer(dataset.current())),
+            None => Outcome::Error((Status::InternalServerError, ())),
+        }
+    }
+}
+
+/// The `search.backend` to suggest from, like `Completer` unaffected by a
+/// reload during the request.
+pub struct Backend(pub Arc<dyn SearchBackend>);
+
+impl Deref for Backend {
+    type Target = dyn SearchBackend;
+
+    fn deref(&self) -> &(dyn SearchBackend + 'static) {
+        &*self.0
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Backend {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, ()> {
+        let rocket = request.rocket();
+        let backend: Option<Arc<dyn SearchBackend>> = match rocket
+            .state::<Settings>()
+            .map(|settings| settings.search.backend)
+        {
+            Some(BackendKind::Memory) => rocket
+                .state::<Arc<Dataset>>()
+                .map(|dataset| dataset.current() as Arc<dyn SearchBackend>),
+            Some(BackendKind::Static) => rocket
+                .state::<Arc<StaticBackend>>()
+                .map(|backend| backend.clone() as Arc<dyn SearchBackend>),
+            None => None,
+        };
+        match backend {
+            Some(backend) => Outcome::Success(Backend(backend)),
+            None => Outcome::Error((Status::InternalServerError, ())),
+        }
+    }
+}
+
+pub fn query_parameter_error(parameter: &str, details: String) -> (Status, Value) {
+    (
+        Status::BadRequest,
+        json!({
+            "type": "QueryParameterFormatError",
+            "title": "Der opstod en fejl under fortolkning af en eller flere query parametre",
+            "details": { parameter: details }
+        }),
+    )
+}
+
+pub fn request_body_error(status: Status, details: String) -> (Status, Value) {
+    (
+        status,
+        json!({
+            "type": "RequestFormatError",
+            "title": "Der opstod en fejl under fortolkning af request body",
+            "details": { "body": details }
+        }),
+    )
+}
+
+pub fn resource_not_found(id: &str) -> (Status, Value) {
+    (
+        Status::NotFound,
+        json!({
+            "type": "ResourceNotFoundError",
+            "title": "The resource was not found",
+            "details": { "id": id }
+        }),
+    )
+}
+
+pub fn parse_uuid(id: &str) -> Result<Uuid, (Status, Value)> {
+    Uuid::parse_str(id).map_err(|_| {
+        (
+            Status::BadRequest,
+            json!({
+                "type": "ResourcePathFormatError",
+                "title": "Der opstod en fejl under fortolkning af URL'en",
+                "details": { "id": format!("{} er ikke et gyldigt UUID", id) }
+            }),
+        )
+    })
+}
+
+/// Parses the mutually exclusive `cirkel`, `polygon` and `bbox` parameters,
+/// given in `srid`, into WGS84.
+pub fn parse_area(
+    cirkel: Option<String>,
+    polygon: Option<String>,
+    bbox: Option<String>,
+    srid: Srid,
+) -> Result<Option<Area>, (Status, Value)> {
+    let (parameter, area) = match (cirkel, polygon, bbox) {
+        (None, None, None) => return Ok(None),
+        (Some(cirkel), None, None) => ("cirkel", cirkel.parse().map(Area::Circle)),
+        (None, Some(polygon), None) => ("polygon", polygon.parse().map(Area::Polygon)),
+        (None, None, Some(bbox)) => ("bbox", bbox.parse().map(Area::BoundingBox)),
+        _ => {
+            return Err(query_parameter_error(
+                "cirkel",
+                "cirkel, polygon og bbox kan ikke kombineres".to_string(),
+            ))
+        }
+    };
+    let area = area
+        .map_err(|details| query_parameter_error(parameter, details))?
+        .unproject(srid);
+    if !area.is_in_range() {
+        return Err(query_parameter_error(
+            parameter,
+            "koordinaterne ligger uden for det gyldige område".to_string(),
+        ));
+    }
+    Ok(Some(area))
+}
+
+/// Parses an optional query parameter, answering 400 when it is malformed.
+pub fn parse_parameter<T: FromStr>(
+    parameter: &str,
+    value: Option<String>,
+) -> Result<Option<T>, (Status, Value)> {
+    value
+        .map(|value| {
+            value
+                .parse()
+                .map_err(|_| query_parameter_error(parameter, format!("Ugyldig værdi: {}", value)))
+        })
+        .transpose()
+}
+
+/// DAWA's 1-based `side` and `per_side` pagination.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Page {
+    pub side: usize,
+    pub per_side: usize,
+}
+
+impl Page {
+    /// Reads the `side` and `per_side` parameters, answering 400 unless they
+    /// are positive and `per_side` is at most `search.max_per_side`.
+    pub fn parse(
+        side: Option<String>,
+        per_side: Option<String>,
+        settings: &SearchSettings,
+    ) -> Result<Page, (Status, Value)> {
+        let side = parse_parameter("side", side)?.unwrap_or(1);
+        let per_side = parse_parameter("per_side", per_side)?.unwrap_or(settings.per_side);
+        if side == 0 {
+            return Err(query_parameter_error(
+                "side",
+                "side skal være mindst 1".to_string(),
+            ));
+        }
+        if per_side == 0 || per_side > settings.max_per_side {
+            return Err(query_parameter_error(
+                "per_side",
+                format!("per_side skal være mellem 1 og {}", settings.max_per_side),
+            ));
+        }
+        Ok(Page { side, per_side })
+    }
+
+    /// The number of results before this page.
+    pub fn offset(&self) -> usize {
+        (self.side - 1).saturating_mul(self.per_side)
+    }
+
+    /// The number of results up to and including this page.
+    pub fn end(&self) -> usize {
+        self.side.saturating_mul(self.per_side)
+    }
+
+    /// The results on this page, and the number of results in all when
+    /// `count` is set.
+    pub fn apply<T>(
+        &self,
+        results: impl Iterator<Item = T>,
+        count: bool,
+    ) -> (Vec<T>, Option<usize>) {
+        let mut page = Vec::new();
+        let mut total = 0;
+        for result in results {
+            if total >= self.offset() && page.len() < self.per_side {
+                page.push(result);
+            }
+            total += 1;
+            if !count && page.len() == self.per_side {
+                break;
+            }
+        }
+        (page, count.then_some(total))
+    }
+}
+
+/// A page of search results. With the total known, `X-Total-Count` and a
+/// `Link` header with the first, previous, next and last page are added.
+pub struct Paged {
+    body: Formatted,
+    page: Option<(Page, usize)>,
+}
+
+impl Paged {
+    pub fn new(body: impl Into<Formatted>, page: Page, total: Option<usize>) -> Paged {
+        Paged {
+            body: body.into(),
+            page: total.map(|total| (page, total)),
+        }
+    }
+}
+
+impl From<(Status, Value)> for Paged {
+    fn from(error: (Status, Value)) -> Self {
+        Paged {
+            body: error.into(),
+            page: None,
+        }
+    }
+}
+
+impl<'r> Responder<'r, 'static> for Paged {
+    fn respond_to(self, request: &'r Request<'_>) -> response::Result<'static> {
+        let mut response = self.body.respond_to(request)?;
+        if let Some((page, total)) = self.page {
+            response.set_raw_header("X-Total-Count", total.to_string());
+            response.set_raw_header("Link", links(request, page, total));
+        }
+        Ok(response)
+    }
+}
+
+/// RFC 8288 links to the neighbouring pages, keeping the other parameters.
+fn links(request: &Request<'_>, page: Page, total: usize) -> String {
+    let uri = request.uri();
+    let mut query: Vec<&str> = uri
+        .query()
+        .map(|query| query.as_str().split('&').collect())
+        .unwrap_or_default();
+    query.retain(|parameter| !parameter.is_empty() && !parameter.starts_with("side="));
+    let base = format!("{}{}", href_base(request), uri.path());
+    let link = |side: usize, rel: &str| {
+        let mut parameters = query.clone();
+        let side = format!("side={}", side);
+        parameters.push(&side);
+        format!("<{}?{}>; rel=\"{}\"", base, parameters.join("&"), rel)
+    };
+    let last = total.div_ceil(page.per_side).max(1);
+    let mut links = vec![link(1, "first")];
+    if page.side > 1 {
+        links.push(link((page.side - 1).min(last), "prev"));
+    }
+    if page.side < last {
+        links.push(link(page.side + 1, "next"));
+    }
+    links.push(link(last, "last"));
+    links.join(", ")
+}
+
+/// DAWA renders absent values as `null` rather than an empty string.
+pub fn nullable(value: &str) -> Value {
+    if value.is_empty() {
+        Value::Null
+    } else {
+        json!(value)
+    }
+}
+
+/// The `dør` parameter, read from the query directly as Rocket form field
+/// names are restricted to ASCII.
+pub struct Door(pub Option<String>);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Door {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, ()> {
+        Outcome::Success(Door(
+            request
+                .query_value::<String>("dør")
+                .and_then(|door| door.ok()),
+        ))
+    }
+}
+
+/// The `struktur` parameter.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum Structure {
+    Mini,
+    #[default]
+    Nested,
+    Flat,
+}
+
+impl FromStr for Structure {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "mini" => Ok(Structure::Mini),
+            "nestet" => Ok(Structure::Nested),
+            "flad" => Ok(Structure::Flat),
+            _ => Err(format!("{} er ikke en gyldig struktur", s)),
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use std::sync::Arc;
+
+    use rocket::{figment::Figment, local::blocking::Client};
+
+    use crate::address_completer::fixture;
+    use crate::config::Settings;
+    use crate::dataset::Dataset;
+
+    pub fn client() -> Client {
+        client_with(rocket::Config::figment())
+    }
+
+    pub fn client_with(figment: Figment) -> Client {
+        let address_completer = fixture();
+        let settings: Settings = figment.extract().unwrap();
+        let dataset = Dataset::new(settings.clone(), address_completer);
+        let rocket = rocket::custom(figment)
+            .manage(settings)
+            .manage(Arc::new(dataset))
+            .mount("/", super::routes());
+        Client::tracked(rocket).unwrap()
+    }
+}
@@ -0,0 +1,196 @@
+use rocket::{http::Status, serde::json::Value, State};
+use serde_json::json;
+
+use super::{nullable, parse_parameter, resource_not_found, Completer, HrefBase, Page, Paged};
+use crate::address_completer::{Municipality, MunicipalityFilter};
+use crate::backends::SearchBackend;
+use crate::config::Settings;
+
+#[derive(FromForm)]
+pub struct MunicipalityQuery {
+    q: Option<String>,
+    regionskode: Option<String>,
+    side: Option<String>,
+    per_side: Option<String>,
+}
+
+impl MunicipalityQuery {
+    fn filter(&self, q: Option<String>) -> Result<MunicipalityFilter, (Status, Value)> {
+        Ok(MunicipalityFilter {
+            q,
+            region_code: parse_parameter("regionskode", self.regionskode.clone())?,
+        })
+    }
+}
+
+//GET https://api.dataforsyningen.dk/kommuner?regionskode=1084
+#[get("/kommuner?<query..>")]
+pub fn search(
+    completer: Completer,
+    settings: &State<Settings>,
+    href: HrefBase,
+    query: MunicipalityQuery,
+) -> Paged {
+    let filter = match query.filter(query.q.clone()) {
+        Ok(filter) => filter,
+        Err(error) => return error.into(),
+    };
+    let page = match Page::parse(query.side.clone(), query.per_side.clone(), &settings.search) {
+        Ok(page) => page,
+        Err(error) => return error.into(),
+    };
+    let (result, total) = page.apply(
+        completer.search_municipalities(&filter),
+        settings.search.total_count,
+    );
+    let result: Vec<Value> = result
+        .into_iter()
+        .map(|municipality| document(municipality, &href.0))
+        .collect();
+
+    Paged::new(json!(result), page, total)
+}
+
+//GET https://api.dataforsyningen.dk/kommuner/autocomplete?q=lol
+#[get("/kommuner/autocomplete?<query..>")]
+pub fn autocomplete(
+    completer: Completer,
+    settings: &State<Settings>,
+    href: HrefBase,
+    query: MunicipalityQuery,
+) -> Paged {
+    let filter = match query.filter(None) {
+        Ok(filter) => filter,
+        Err(error) => return error.into(),
+    };
+    let page = match Page::parse(query.side.clone(), query.per_side.clone(), &settings.search) {
+        Ok(page) => page,
+        Err(error) => return error.into(),
+    };
+    let prefix = query.q.clone().unwrap_or_default();
+    let (result, total) = page.apply(
+        completer.complete_municipalities(&prefix, &filter),
+        settings.search.total_count,
+    );
+    let result: Vec<Value> = result
+        .into_iter()
+        .map(|municipality| {
+            json!({
+                "tekst": municipality.name,
+                "kommune": {
+                    "href": href_for(&href.0, municipality.code),
+                    "kode": format!("{:04}", municipality.code),
+                    "navn": municipality.name,
+                }
+            })
+        })
+        .collect();
+
+    Paged::new(json!(result), page, total)
+}
+
+//GET https://api.dataforsyningen.dk/kommuner/0101
+#[get("/kommuner/<kode>")]
+pub fn get(completer: Completer, href: HrefBase, kode: &str) -> (Status, Value) {
+    match kode
+        .parse()
+        .ok()
+        .and_then(|code| completer.municipality(code))
+    {
+        Some(municipality) => (Status::Ok, document(municipality, &href.0)),
+        None => resource_not_found(kode),
+    }
+}
+
+pub fn href_for(base: &str, code: i32) -> String {
+    format!("{}/kommuner/{:04}", base, code)
+}
+
+/// The `{href, kode, navn}` object other resources use to refer to a kommune.
+pub fn reference(backend: &dyn SearchBackend, code: i32, base: &str) -> Value {
+    json!({
+        "href": href_for(base, code),
+        "kode": format!("{:04}", code),
+        "navn": nullable(backend.municipality_name(code)),
+    })
+}
+
+fn document(municipality: &Municipality, base: &str) -> Value {
+    json!({
+        "href": href_for(base, municipality.code),
+        "kode": format!("{:04}", municipality.code),
+        "navn": nullable(&municipality.name),
+        "regionskode": if municipality.region_code == 0 {
+            Value::Null
+        } else {
+            json!(format!("{:04}", municipality.region_code))
+        },
+        "region": if municipality.region_code == 0 {
+            Value::Null
+        } else {
+            json!({
+                "href": format!("{}/regioner/{:04}", base, municipality.region_code),
+                "kode": format!("{:04}", municipality.region_code),
+                "navn": municipality.region_name,
+            })
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use rocket::http::Status;
+    use serde_json::Value;
+
+    use crate::routes::test::client;
+
+    #[test]
+    fn test_search() {
+        let client = client();
+        let body: Value = client
+            .get("/kommuner?regionskode=1084")
+            .dispatch()
+            .into_json()
+            .unwrap();
+        assert_eq!(body.as_array().unwrap().len(), 3);
+        assert_eq!(body[0]["navn"], "København");
+        assert_eq!(body[0]["region"]["navn"], "Region Hovedstaden");
+
+        let body: Value = client
+            .get("/kommuner?q=lolland")
+            .dispatch()
+            .into_json()
+            .unwrap();
+        assert_eq!(body[0]["kode"], "0360");
+
+        let response = client.get("/kommuner?regionskode=hovedstaden").dispatch();
+        assert_eq!(response.status(), Status::BadRequest);
+    }
+
+    #[test]
+    fn test_get_and_autocomplete() {
+        let client = client();
+        let response = client.get("/kommuner/0147").dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        let body: Value = response.into_json().unwrap();
+        assert_eq!(body["navn"], "Frederiksberg");
+        assert_eq!(body["regionskode"], "1084");
+
+        let response = client.get("/kommuner/147").dispatch();
+        assert_eq!(response.status(), Status::Ok);
+
+        let response = client.get("/kommuner/9999").dispatch();
+        assert_eq!(response.status(), Status::NotFound);
+
+        let body: Value = client
+            .get("/kommuner/autocomplete?q=aar")
+            .dispatch()
+            .into_json()
+            .unwrap();
+        assert_eq!(body[0]["tekst"], "Aarhus");
+        assert_eq!(
+            body[0]["kommune"]["href"],
+            "http://127.0.0.1:8000/kommuner/0751"
+        );
+    }
+}
@@ -0,0 +1,178 @@
+use rocket::{http::Status, serde::json::Value, State};
+use serde_json::json;
+
+use super::{
+    municipalities, parse_parameter, resource_not_found, Completer, HrefBase, Page, Paged,
+};
+use crate::address_completer::{AddressCompleter, PostalCodeFilter};
+use crate::config::Settings;
+use crate::postal_code::PostalCode;
+
+#[derive(FromForm)]
+pub struct PostalCodeQuery {
+    q: Option<String>,
+    kommunekode: Option<String>,
+    stormodtagere: Option<bool>,
+    side: Option<String>,
+    per_side: Option<String>,
+}
+
+impl PostalCodeQuery {
+    fn filter(&self, q: Option<String>) -> Result<PostalCodeFilter, (Status, Value)> {
+        Ok(PostalCodeFilter {
+            q,
+            municipal_code: parse_parameter("kommunekode", self.kommunekode.clone())?,
+            large_recipients: self.stormodtagere.unwrap_or(false),
+        })
+    }
+}
+
+//GET https://api.dataforsyningen.dk/postnumre?kommunekode=0101
+#[get("/postnumre?<query..>")]
+pub fn search(
+    completer: Completer,
+    settings: &State<Settings>,
+    href: HrefBase,
+    query: PostalCodeQuery,
+) -> Paged {
+    let filter = match query.filter(query.q.clone()) {
+        Ok(filter) => filter,
+        Err(error) => return error.into(),
+    };
+    let page = match Page::parse(query.side.clone(), query.per_side.clone(), &settings.search) {
+        Ok(page) => page,
+        Err(error) => return error.into(),
+    };
+    let (result, total) = page.apply(
+        completer.search_postal_codes(&filter),
+        settings.search.total_count,
+    );
+    let result: Vec<Value> = result
+        .into_iter()
+        .map(|postal_code| document(postal_code, &completer, &href.0))
+        .collect();
+
+    Paged::new(json!(result), page, total)
+}
+
+//GET https://api.dataforsyningen.dk/postnumre/autocomplete?q=valb
+#[get("/postnumre/autocomplete?<query..>")]
+pub fn autocomplete(
+    completer: Completer,
+    settings: &State<Settings>,
+    href: HrefBase,
+    query: PostalCodeQuery,
+) -> Paged {
+    let filter = match query.filter(None) {
+        Ok(filter) => filter,
+        Err(error) => return error.into(),
+    };
+    let page = match Page::parse(query.side.clone(), query.per_side.clone(), &settings.search) {
+        Ok(page) => page,
+        Err(error) => return error.into(),
+    };
+    let prefix = query.q.clone().unwrap_or_default();
+    let (result, total) = page.apply(
+        completer.complete_postal_codes(&prefix, &filter),
+        settings.search.total_count,
+    );
+    let result: Vec<Value> = result
+        .into_iter()
+        .map(|postal_code| {
+            json!({
+                "tekst": format!("{} {}", postal_code.nr, postal_code.name),
+                "postnummer": {
+                    "href": format!("{}/postnumre/{}", href.0, postal_code.nr),
+                    "nr": postal_code.nr,
+                    "navn": postal_code.name,
+                    "stormodtager": postal_code.large_recipient,
+                }
+            })
+        })
+        .collect();
+
+    Paged::new(json!(result), page, total)
+}
+
+//GET https://api.dataforsyningen.dk/postnumre/2500
+#[get("/postnumre/<nr>")]
+pub fn get(completer: Completer, href: HrefBase, nr: &str) -> (Status, Value) {
+    match completer.postal_code(nr) {
+        Some(postal_code) => (Status::Ok, document(postal_code, &completer, &href.0)),
+        None => resource_not_found(nr),
+    }
+}
+
+fn document(postal_code: &PostalCode, completer: &AddressCompleter, base: &str) -> Value {
+    json!({
+        "href": format!("{}/postnumre/{}", base, postal_code.nr),
+        "nr": postal_code.nr,
+        "navn": postal_code.name,
+        "stormodtager": postal_code.large_recipient,
+        "stormodtageradresser": if postal_code.large_recipient {
+            json!(postal_code
+                .large_recipient_addresses
+                .iter()
+                .map(|id| format!("{}/adgangsadresser/{}", base, id))
+                .collect::<Vec<String>>())
+        } else {
+            Value::Null
+        },
+        "kommuner": postal_code
+            .municipal_codes
+            .keys()
+            .map(|code| municipalities::reference(completer, *code, base))
+            .collect::<Vec<Value>>(),
+        "adresser": postal_code.addresses(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use rocket::http::Status;
+    use serde_json::Value;
+
+    use crate::routes::test::client;
+
+    #[test]
+    fn test_search() {
+        let client = client();
+        let body: Value = client
+            .get("/postnumre?kommunekode=0101")
+            .dispatch()
+            .into_json()
+            .unwrap();
+        assert_eq!(body.as_array().unwrap().len(), 3);
+
+        let body: Value = client
+            .get("/postnumre?kommunekode=0101&stormodtagere=true")
+            .dispatch()
+            .into_json()
+            .unwrap();
+        assert_eq!(body.as_array().unwrap().len(), 4);
+        assert_eq!(body[2]["nr"], "1240");
+        assert_eq!(body[2]["stormodtager"], true);
+        assert_eq!(body[2]["stormodtageradresser"].as_array().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_get_and_autocomplete() {
+        let client = client();
+        let response = client.get("/postnumre/2500").dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        let body: Value = response.into_json().unwrap();
+        assert_eq!(body["navn"], "Valby");
+        assert_eq!(body["adresser"], 3);
+        assert_eq!(body["kommuner"][0]["kode"], "0101");
+
+        let response = client.get("/postnumre/9999").dispatch();
+        assert_eq!(response.status(), Status::NotFound);
+
+        let body: Value = client
+            .get("/postnumre/autocomplete?q=val")
+            .dispatch()
+            .into_json()
+            .unwrap();
+        assert_eq!(body[0]["tekst"], "2500 Valby");
+    }
+}
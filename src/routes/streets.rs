@@ -0,0 +1,179 @@
+use rocket::{
+    http::{RawStr, Status},
+    serde::json::Value,
+    State,
+};
+use serde_json::json;
+
+use super::{
+    municipalities, parse_parameter, resource_not_found, Backend, Completer, HrefBase, Page, Paged,
+};
+use crate::address_completer::StreetFilter;
+use crate::backends::SearchBackend;
+use crate::config::Settings;
+use crate::street_index::Street;
+
+#[derive(FromForm)]
+pub struct StreetQuery {
+    q: Option<String>,
+    postnr: Option<String>,
+    kommunekode: Option<String>,
+    side: Option<String>,
+    per_side: Option<String>,
+}
+
+impl StreetQuery {
+    fn filter(&self, q: Option<String>) -> Result<StreetFilter, (Status, Value)> {
+        Ok(StreetFilter {
+            q,
+            zip: self.postnr.clone(),
+            municipal_code: parse_parameter("kommunekode", self.kommunekode.clone())?,
+        })
+    }
+}
+
+//GET https://api.dataforsyningen.dk/vejnavne?q=maribovej&postnr=4960
+#[get("/vejnavne?<query..>")]
+pub fn search(
+    completer: Completer,
+    settings: &State<Settings>,
+    href: HrefBase,
+    query: StreetQuery,
+) -> Paged {
+    let filter = match query.filter(query.q.clone()) {
+        Ok(filter) => filter,
+        Err(error) => return error.into(),
+    };
+    let page = match Page::parse(query.side.clone(), query.per_side.clone(), &settings.search) {
+        Ok(page) => page,
+        Err(error) => return error.into(),
+    };
+    let (result, total) = page.apply(
+        completer.search_streets(&filter),
+        settings.search.total_count,
+    );
+    let result: Vec<Value> = result
+        .into_iter()
+        .map(|street| document(street, &*completer, &href.0))
+        .collect();
+
+    Paged::new(json!(result), page, total)
+}
+
+//GET https://api.dataforsyningen.dk/vejnavne/autocomplete?q=maribo
+#[get("/vejnavne/autocomplete?<query..>")]
+pub fn autocomplete(
+    completer: Completer,
+    settings: &State<Settings>,
+    href: HrefBase,
+    query: StreetQuery,
+) -> Paged {
+    let filter = match query.filter(None) {
+        Ok(filter) => filter,
+        Err(error) => return error.into(),
+    };
+    let page = match Page::parse(query.side.clone(), query.per_side.clone(), &settings.search) {
+        Ok(page) => page,
+        Err(error) => return error.into(),
+    };
+    let prefix = query.q.clone().unwrap_or_default();
+    let (result, total) = page.apply(
+        completer.complete_streets(&prefix, &filter),
+        settings.search.total_count,
+    );
+    let result: Vec<Value> = result
+        .into_iter()
+        .map(|street| {
+            json!({
+                "tekst": street.name,
+                "vejnavn": {
+                    "href": href_for(&href.0, &street.name),
+                    "navn": street.name,
+                }
+            })
+        })
+        .collect();
+
+    Paged::new(json!(result), page, total)
+}
+
+//GET https://api.dataforsyningen.dk/vejnavne/Maribovej
+#[get("/vejnavne/<navn>")]
+pub fn get(backend: Backend, href: HrefBase, navn: &str) -> (Status, Value) {
+    match backend.street(navn) {
+        Some(street) => (Status::Ok, document(street, &*backend, &href.0)),
+        None => resource_not_found(navn),
+    }
+}
+
+pub fn href_for(base: &str, name: &str) -> String {
+    format!("{}/vejnavne/{}", base, RawStr::new(name).percent_encode())
+}
+
+fn document(street: &Street, backend: &dyn SearchBackend, base: &str) -> Value {
+    json!({
+        "href": href_for(base, &street.name),
+        "navn": street.name,
+        "postnumre": street.postal_codes.iter().map(|(nr, (navn, _))| json!({
+            "href": format!("{}/postnumre/{}", base, nr),
+            "nr": nr,
+            "navn": navn,
+        })).collect::<Vec<Value>>(),
+        "kommuner": street
+            .municipal_codes
+            .keys()
+            .map(|code| municipalities::reference(backend, *code, base))
+            .collect::<Vec<Value>>(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use rocket::http::Status;
+    use serde_json::Value;
+
+    use crate::routes::test::client;
+
+    #[test]
+    fn test_search() {
+        let client = client();
+        let body: Value = client
+            .get("/vejnavne?q=maribovej")
+            .dispatch()
+            .into_json()
+            .unwrap();
+        assert_eq!(body.as_array().unwrap().len(), 1);
+        assert_eq!(body[0]["postnumre"].as_array().unwrap().len(), 2);
+        assert_eq!(body[0]["postnumre"][0]["nr"], "2500");
+        assert_eq!(body[0]["kommuner"][1]["kode"], "0360");
+
+        let body: Value = client
+            .get("/vejnavne?postnr=4520")
+            .dispatch()
+            .into_json()
+            .unwrap();
+        assert_eq!(body[0]["navn"], "Hovedgaden");
+    }
+
+    #[test]
+    fn test_autocomplete_and_follow_href() {
+        let client = client();
+        let body: Value = client
+            .get("/vejnavne/autocomplete?q=kron")
+            .dispatch()
+            .into_json()
+            .unwrap();
+        assert_eq!(body[0]["tekst"], "Kronprinsesse Sofies Vej");
+        let href = body[0]["vejnavn"]["href"].as_str().unwrap();
+        assert!(href.ends_with("/vejnavne/Kronprinsesse%20Sofies%20Vej"));
+
+        let path = &href[href.find("/vejnavne").unwrap()..];
+        let response = client.get(path.to_string()).dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        let body: Value = response.into_json().unwrap();
+        assert_eq!(body["postnumre"][0]["navn"], "Frederiksberg");
+
+        let response = client.get("/vejnavne/Ukendt%20Vej").dispatch();
+        assert_eq!(response.status(), Status::NotFound);
+    }
+}
@@ -0,0 +1,449 @@
+use std::{collections::VecDeque, sync::Arc};
+
+use rocket::{
+    data::{Data, Limits, ToByteUnit},
+    http::{ContentType, Status},
+    response::stream::TextStream,
+    serde::json::Value,
+    tokio::task,
+};
+use serde_json::json;
+
+use super::{
+    addresses, nullable, query_parameter_error, request_body_error, Completer, HrefBase, Structure,
+};
+use crate::address_completer::AddressCompleter;
+use crate::address_parser::ParsedAddress;
+use crate::geo::Srid;
+use crate::washing::Candidate;
+
+/// How many candidates a wash returns
+const WASH_RESULTS: usize = 10;
+/// Largest batch body unless `limits.datavask` is configured
+const BATCH_LIMIT: usize = 64;
+/// Rows washed by each blocking task
+const BATCH_CHUNK: usize = 256;
+
+//GET https://api.dataforsyningen.dk/datavask/adresser?betegnelse=Maribovej 15, 1. tv, 2500 Valby
+#[get("/datavask/adresser?<betegnelse>")]
+pub fn wash(completer: Completer, href: HrefBase, betegnelse: Option<String>) -> (Status, Value) {
+    let Some(text) = betegnelse.filter(|text| !text.trim().is_empty()) else {
+        return query_parameter_error(
+            "betegnelse",
+            "Missing required parameter betegnelse".to_string(),
+        );
+    };
+    let washed = completer.wash(&text);
+    let result: Vec<Value> = washed
+        .candidates
+        .iter()
+        .take(WASH_RESULTS)
+        .map(|candidate| document(candidate, &washed.parsed, &completer, &href.0))
+        .collect();
+
+    (
+        Status::Ok,
+        json!({
+            "kategori": washed.category.to_string(),
+            "resultater": result,
+        }),
+    )
+}
+
+pub fn document(
+    candidate: &Candidate,
+    parsed: &ParsedAddress,
+    completer: &AddressCompleter,
+    base: &str,
+) -> Value {
+    let address = &candidate.address;
+    let differences = &candidate.differences;
+    json!({
+        "adresse": addresses::document(address, Structure::Mini, Srid::default(), completer, base),
+        "vaskeresultat": {
+            "variant": {
+                "vejnavn": address.street,
+                "husnr": address.number,
+                "etage": nullable(&address.floor),
+                "dør": nullable(&address.door),
+                "supplerendebynavn": nullable(&address.placename),
+                "postnr": address.zip,
+                "postnrnavn": address.city,
+            },
+            "afstand": differences.total(),
+            "forskelle": {
+                "vejnavn": differences.street,
+                "husnr": differences.number,
+                "etage": differences.floor,
+                "dør": differences.door,
+                "supplerendebynavn": differences.placename,
+                "postnr": differences.zip,
+                "postnrnavn": differences.city,
+            },
+            "parsetadresse": {
+                "vejnavn": parsed.street,
+                "husnr": parsed.number,
+                "etage": parsed.floor,
+                "dør": parsed.door,
+                "supplerendebynavn": parsed.placename,
+                "postnr": parsed.zip,
+                "postnrnavn": parsed.city,
+            },
+            "score": candidate.score,
+        },
+    })
+}
+
+/// How a batch is written, responses use the format of the request.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum BatchFormat {
+    Csv,
+    Ndjson,
+}
+
+impl BatchFormat {
+    fn of(content_type: Option<&ContentType>) -> Self {
+        match content_type {
+            Some(content_type)
+                if content_type.is_json()
+                    || ["x-ndjson", "ndjson", "jsonl"].contains(&content_type.sub().as_str()) =>
+            {
+                BatchFormat::Ndjson
+            }
+            _ => BatchFormat::Csv,
+        }
+    }
+
+    fn content_type(self) -> ContentType {
+        match self {
+            BatchFormat::Csv => ContentType::CSV,
+            BatchFormat::Ndjson => ContentType::new("application", "x-ndjson"),
+        }
+    }
+
+    /// The addresses in a body, a CSV file with a `betegnelse` column (the
+    /// first column if there is none) or one JSON string or object with a
+    /// `betegnelse` per line.
+    fn read(self, body: &str) -> Result<Vec<String>, String> {
+        match self {
+            BatchFormat::Csv => {
+                let mut reader = csv::Reader::from_reader(body.as_bytes());
+                let column = reader
+                    .headers()
+                    .map_err(|error| error.to_string())?
+                    .iter()
+                    .position(|header| header.trim() == "betegnelse")
+                    .unwrap_or(0);
+                reader
+                    .records()
+                    .map(|record| {
+                        let record = record.map_err(|error| error.to_string())?;
+                        Ok(record.get(column).unwrap_or("").to_string())
+                    })
+                    .collect()
+            }
+            BatchFormat::Ndjson => body
+                .lines()
+                .enumerate()
+                .filter(|(_, line)| !line.trim().is_empty())
+                .map(|(number, line)| {
+                    let error = || format!("Line {} has no betegnelse", number + 1);
+                    match serde_json::from_str(line).map_err(|_| error())? {
+                        Value::String(text) => Ok(text),
+                        Value::Object(object) => object
+                            .get("betegnelse")
+                            .and_then(Value::as_str)
+                            .map(str::to_string)
+                            .ok_or_else(error),
+                        _ => Err(error()),
+                    }
+                })
+                .collect(),
+        }
+    }
+
+    fn header(self) -> Option<String> {
+        match self {
+            BatchFormat::Csv => Some(csv_line(&[
+                "betegnelse",
+                "kategori",
+                "score",
+                "id",
+                "adressebetegnelse",
+            ])),
+            BatchFormat::Ndjson => None,
+        }
+    }
+
+    /// A last row saying the batch was cut short: kategori `fejl` in CSV, an
+    /// error object in NDJSON, like the `/adresser` export ends with.
+    fn error(self, details: &str) -> String {
+        let title = "Vasken blev afbrudt";
+        match self {
+            BatchFormat::Csv => csv_line(&["", "fejl", "", "", &format!("{}: {}", title, details)]),
+            BatchFormat::Ndjson => format!(
+                "{}\n",
+                json!({
+                    "type": "InternalServerError",
+                    "title": title,
+                    "details": details,
+                })
+            ),
+        }
+    }
+
+    fn line(self, text: &str, completer: &AddressCompleter) -> String {
+        let washed = completer.wash(text);
+        let best = washed.candidates.first();
+        let category = washed.category.to_string();
+        let score = best.map(|candidate| candidate.score);
+        let id = best.map(|candidate| candidate.address.id.to_string());
+        let display_name = best.map(|candidate| candidate.address.display_name());
+        match self {
+            BatchFormat::Csv => csv_line(&[
+                text,
+                &category,
+                &score
+                    .map(|score| format!("{:.3}", score))
+                    .unwrap_or_default(),
+                id.as_deref().unwrap_or(""),
+                display_name.as_deref().unwrap_or(""),
+            ]),
+            BatchFormat::Ndjson => format!(
+                "{}\n",
+                json!({
+                    "betegnelse": text,
+                    "kategori": category,
+                    "score": score,
+                    "id": id,
+                    "adressebetegnelse": display_name,
+                })
+            ),
+        }
+    }
+}
+
+fn csv_line(fields: &[&str]) -> String {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    writer.write_record(fields).unwrap();
+    String::from_utf8(writer.into_inner().unwrap()).unwrap()
+}
+
+//POST /datavask/adresser with a CSV or NDJSON body of addresses
+#[post("/datavask/adresser", data = "<body>")]
+pub async fn wash_batch(
+    completer: Completer,
+    content_type: Option<&ContentType>,
+    limits: &Limits,
+    body: Data<'_>,
+) -> Result<(ContentType, TextStream![String]), (Status, Value)> {
+    let format = BatchFormat::of(content_type);
+    let limit = limits
+        .get("datavask")
+        .unwrap_or_else(|| BATCH_LIMIT.mebibytes());
+    let body = body
+        .open(limit)
+        .into_string()
+        .await
+        .map_err(|error| request_body_error(Status::BadRequest, error.to_string()))?;
+    if !body.is_complete() {
+        return Err(request_body_error(
+            Status::PayloadTooLarge,
+            format!("The body is larger than {}", limit),
+        ));
+    }
+    let texts = format
+        .read(&body)
+        .map_err(|error| request_body_error(Status::BadRequest, error))?;
+
+    let completer = completer.0;
+    let stream = washed_lines(format, texts, move |text| format.line(text, &completer));
+    Ok((format.content_type(), stream))
+}
+
+/// The lines of a batch, each chunk washed with `line` on the blocking pool,
+/// a few more at a time than there are cores, and written in the order they
+/// came in.
+fn washed_lines(
+    format: BatchFormat,
+    texts: Vec<String>,
+    line: impl Fn(&str) -> String + Send + Sync + 'static,
+) -> TextStream![String] {
+    let line = Arc::new(line);
+    let parallelism = std::thread::available_parallelism().map_or(4, |n| n.get()) * 2;
+    let mut chunks = texts
+        .chunks(BATCH_CHUNK)
+        .map(|chunk| chunk.to_vec())
+        .collect::<Vec<Vec<String>>>()
+        .into_iter();
+    TextStream! {
+        if let Some(header) = format.header() {
+            yield header;
+        }
+        let mut running = VecDeque::new();
+        loop {
+            while running.len() < parallelism {
+                let Some(chunk) = chunks.next() else {
+                    break;
+                };
+                let line = line.clone();
+                running.push_back(task::spawn_blocking(move || {
+                    chunk.iter().map(|text| line(text)).collect::<String>()
+                }));
+            }
+            let Some(lines) = running.pop_front() else {
+                break;
+            };
+            match lines.await {
+                Ok(lines) => yield lines,
+                Err(error) => {
+                    error!("Washing a batch failed: {}", error);
+                    yield format.error(&error.to_string());
+                    break;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rocket::{
+        futures::StreamExt,
+        http::{ContentType, Status},
+    };
+    use serde_json::Value;
+
+    use super::*;
+    use crate::routes::test::client;
+
+    #[test]
+    fn test_wash() {
+        let client = client();
+        let body: Value = client
+            .get("/datavask/adresser?betegnelse=Maribovej%2015%2C%201.%20tv%2C%202500%20Valby")
+            .dispatch()
+            .into_json()
+            .unwrap();
+        assert_eq!(body["kategori"], "A");
+        assert_eq!(body["resultater"][0]["adresse"]["etage"], "1");
+        assert_eq!(body["resultater"][0]["vaskeresultat"]["afstand"], 0);
+
+        let body: Value = client
+            .get("/datavask/adresser?betegnelse=Maribovej%2015%20st%20th%202500%20Valbi")
+            .dispatch()
+            .into_json()
+            .unwrap();
+        assert_eq!(body["kategori"], "B");
+        assert_eq!(body["resultater"][0]["adresse"]["dør"], "th");
+        assert_eq!(
+            body["resultater"][0]["vaskeresultat"]["forskelle"]["postnrnavn"],
+            1
+        );
+        assert_eq!(
+            body["resultater"][0]["vaskeresultat"]["parsetadresse"]["postnrnavn"],
+            "Valbi"
+        );
+    }
+
+    #[test]
+    fn test_missing_betegnelse() {
+        let client = client();
+        let response = client.get("/datavask/adresser").dispatch();
+        assert_eq!(response.status(), Status::BadRequest);
+        let body: Value = response.into_json().unwrap();
+        assert_eq!(body["type"], "QueryParameterFormatError");
+    }
+
+    #[test]
+    fn test_wash_batch_csv() {
+        let client = client();
+        let body = "id,betegnelse\n\
+            1,\"Maribovej 15, 1. tv, 2500 Valby\"\n\
+            2,Hovedgaden 1\n\
+            3,Mariboevej 10 4960 Holeby\n";
+        let response = client
+            .post("/datavask/adresser")
+            .header(ContentType::CSV)
+            .body(body)
+            .dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(response.content_type(), Some(ContentType::CSV));
+        let body = response.into_string().unwrap();
+        let lines: Vec<&str> = body.lines().collect();
+        assert_eq!(lines.len(), 4);
+        assert_eq!(lines[0], "betegnelse,kategori,score,id,adressebetegnelse");
+        assert!(lines[1].starts_with(
+            "\"Maribovej 15, 1. tv, 2500 Valby\",A,1.000,0a3f509f-96d9-32b8-e044-0003ba298018,"
+        ));
+        assert!(lines[2].starts_with("Hovedgaden 1,C,"));
+        assert!(lines[3].starts_with("Mariboevej 10 4960 Holeby,B,"));
+    }
+
+    #[test]
+    fn test_wash_batch_ndjson() {
+        let client = client();
+        let rows: String = (0..1000)
+            .map(|i| {
+                if i % 2 == 0 {
+                    "\"Maribovej 15 st th 2500 Valby\"\n".to_string()
+                } else {
+                    format!("{{\"betegnelse\": \"Maribovej {} 4960 Holeby\"}}\n", i % 4)
+                }
+            })
+            .collect();
+        let response = client
+            .post("/datavask/adresser")
+            .header(ContentType::new("application", "x-ndjson"))
+            .body(rows)
+            .dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        let body = response.into_string().unwrap();
+        let lines: Vec<Value> = body
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 1000);
+        assert_eq!(lines[0]["kategori"], "A");
+        assert_eq!(lines[0]["id"], "0a3f509f-96d8-32b8-e044-0003ba298018");
+        assert_eq!(lines[999]["betegnelse"], "Maribovej 3 4960 Holeby");
+        assert_eq!(lines[999]["kategori"], "A");
+
+        let response = client
+            .post("/datavask/adresser")
+            .header(ContentType::new("application", "x-ndjson"))
+            .body("[1, 2]\n")
+            .dispatch();
+        assert_eq!(response.status(), Status::BadRequest);
+    }
+
+    #[test]
+    fn test_wash_batch_failure() {
+        let texts: Vec<String> = (0..BATCH_CHUNK * 3).map(|i| i.to_string()).collect();
+        let line = |text: &str| {
+            assert_ne!(text, BATCH_CHUNK.to_string(), "washing failed");
+            format!("{}\n", text)
+        };
+        let runtime = rocket::tokio::runtime::Runtime::new().unwrap();
+
+        let lines = runtime.block_on(
+            washed_lines(BatchFormat::Ndjson, texts.clone(), line)
+                .0
+                .collect::<String>(),
+        );
+        let lines: Vec<&str> = lines.lines().collect();
+        assert_eq!(lines.len(), BATCH_CHUNK + 1);
+        let error: Value = serde_json::from_str(lines[BATCH_CHUNK]).unwrap();
+        assert_eq!(error["type"], "InternalServerError");
+
+        let lines = runtime.block_on(
+            washed_lines(BatchFormat::Csv, texts, line)
+                .0
+                .collect::<String>(),
+        );
+        let lines: Vec<&str> = lines.lines().collect();
+        assert_eq!(lines[0], "betegnelse,kategori,score,id,adressebetegnelse");
+        assert_eq!(lines.len(), BATCH_CHUNK + 2);
+        assert!(lines[BATCH_CHUNK + 1].starts_with(",fejl,,,"));
+    }
+}
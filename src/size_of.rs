@@ -27,6 +27,15 @@ impl SizeOf for String {
     }
 }
 
+impl<T> SizeOf for Option<T>
+where
+    T: SizeOf,
+{
+    fn size_of(&self) -> usize {
+        self.as_ref().map(|v| v.size_of()).unwrap_or(0)
+    }
+}
+
 impl<T> SizeOf for Vec<T>
 where
     T: SizeOf,
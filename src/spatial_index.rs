@@ -0,0 +1,224 @@
+use std::{collections::BTreeMap, iter, sync::Arc};
+
+use crate::address::Address;
+use crate::geo::{Area, BoundingBox, Point};
+
+/// Grid cell size in degrees, roughly 1.1km x 0.6km in Denmark
+const CELL_SIZE: f64 = 0.01;
+
+type Cell = (i32, i32);
+
+/// A uniform grid over access address coordinates.
+pub struct SpatialIndex {
+    cells: BTreeMap<Cell, Vec<Arc<Address>>>,
+    /// The lowest and highest column and row holding addresses, or having
+    /// held them
+    bounds: Option<(Cell, Cell)>,
+}
+
+impl SpatialIndex {
+    pub fn new() -> Self {
+        SpatialIndex {
+            cells: BTreeMap::new(),
+            bounds: None,
+        }
+    }
+
+    fn cell(point: &Point) -> Cell {
+        (
+            (point.x / CELL_SIZE).floor() as i32,
+            (point.y / CELL_SIZE).floor() as i32,
+        )
+    }
+
+    pub fn insert(&mut self, address: &Arc<Address>) {
+        if let Some(point) = &address.coordinates {
+            let cell = Self::cell(point);
+            self.bounds = Some(match self.bounds {
+                Some((low, high)) => (
+                    (low.0.min(cell.0), low.1.min(cell.1)),
+                    (high.0.max(cell.0), high.1.max(cell.1)),
+                ),
+                None => (cell, cell),
+            });
+            self.cells.entry(cell).or_default().push(address.clone());
+        }
+    }
+
+    pub fn remove(&mut self, address: &Address) {
+        if let Some(point) = &address.coordinates {
+            let cell = Self::cell(point);
+            if let Some(addresses) = self.cells.get_mut(&cell) {
+                addresses.retain(|other| other.id != address.id);
+                if addresses.is_empty() {
+                    self.cells.remove(&cell);
+                }
+            }
+        }
+    }
+
+    /// All addresses whose coordinates fall inside `area`.
+    pub fn within<'a>(&'a self, area: &'a Area) -> impl Iterator<Item = &'a Arc<Address>> + 'a {
+        let BoundingBox { min, max } = area.bounding_box();
+        let (min, max) = (Self::cell(&min), Self::cell(&max));
+        // Only the cells that can hold addresses are looked at, however large
+        // the area
+        let (low, high) = self.bounds.unwrap_or(((1, 1), (0, 0)));
+        let (min, max) = (
+            (min.0.max(low.0), min.1.max(low.1)),
+            (max.0.min(high.0), max.1.min(high.1)),
+        );
+        (min.0..=max.0)
+            .filter(move |_| min.1 <= max.1)
+            .flat_map(move |x| self.cells.range((x, min.1)..=(x, max.1)))
+            .flat_map(|(_, addresses)| addresses)
+            .filter(|address| {
+                address
+                    .coordinates
+                    .as_ref()
+                    .is_some_and(|point| area.contains(point))
+            })
+    }
+
+    /// Addresses with coordinates nearest `point` first, looking at rings of
+    /// cells further and further out only as far as needed.
+    pub fn nearest(&self, point: Point) -> impl Iterator<Item = &Arc<Address>> + '_ {
+        let center = Self::cell(&point);
+        // The rings holding the nearest and the farthest cell that can hold
+        // addresses
+        let (mut ring, last) = self.bounds.map_or((0, -1), |(low, high)| {
+            let before = [low.0 - center.0, center.0 - high.0];
+            let below = [low.1 - center.1, center.1 - high.1];
+            (
+                before.into_iter().chain(below).fold(0, i32::max),
+                (center.0 - low.0)
+                    .max(high.0 - center.0)
+                    .max(center.1 - low.1)
+                    .max(high.1 - center.1),
+            )
+        });
+        // Farthest first, so the nearest is popped
+        let mut found: Vec<(f64, &Arc<Address>)> = Vec::new();
+        iter::from_fn(move || loop {
+            let reach = if ring > last {
+                f64::INFINITY
+            } else {
+                Self::reach(point, center, ring)
+            };
+            if let Some(&(distance, address)) = found.last() {
+                if distance <= reach {
+                    found.pop();
+                    return Some(address);
+                }
+            }
+            if ring > last {
+                return None;
+            }
+            found.extend(self.ring(center, ring).filter_map(|address| {
+                let distance = point.distance(address.coordinates.as_ref()?);
+                Some((distance, address))
+            }));
+            found.sort_by(|a, b| b.0.total_cmp(&a.0));
+            ring += 1;
+        })
+    }
+
+    /// How far from `point` an address in `ring` or beyond is at least.
+    fn reach(point: Point, center: Cell, ring: i32) -> f64 {
+        if ring == 0 {
+            return 0.0;
+        }
+        let (west, east) = (
+            (center.0 - ring + 1) as f64 * CELL_SIZE,
+            (center.0 + ring) as f64 * CELL_SIZE,
+        );
+        let (south, north) = (
+            (center.1 - ring + 1) as f64 * CELL_SIZE,
+            (center.1 + ring) as f64 * CELL_SIZE,
+        );
+        [
+            Point::new(west, point.y),
+            Point::new(east, point.y),
+            Point::new(point.x, south),
+            Point::new(point.x, north),
+        ]
+        .iter()
+        .map(|edge| point.distance(edge))
+        .fold(f64::INFINITY, f64::min)
+    }
+
+    /// The addresses in the cells `ring` cells away from `center`.
+    fn ring(&self, center: Cell, ring: i32) -> impl Iterator<Item = &Arc<Address>> + '_ {
+        let (min, max) = (
+            (center.0 - ring, center.1 - ring),
+            (center.0 + ring, center.1 + ring),
+        );
+        let (low, high) = self.bounds.unwrap_or(((1, 1), (0, 0)));
+        (min.0.max(low.0)..=max.0.min(high.0))
+            .flat_map(move |x| {
+                let rows = min.1.max(low.1)..=max.1.min(high.1);
+                // Only the top and bottom cell of the columns in between
+                let step = if x == min.0 || x == max.0 {
+                    1
+                } else {
+                    2 * ring as usize
+                };
+                (min.1..=max.1)
+                    .step_by(step.max(1))
+                    .filter(move |y| rows.contains(y))
+                    .map(move |y| (x, y))
+            })
+            .filter_map(|cell| self.cells.get(&cell))
+            .flatten()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn address(x: f64, y: f64) -> Arc<Address> {
+        Arc::new(Address {
+            coordinates: Some(Point::new(x, y)),
+            ..Address::default()
+        })
+    }
+
+    #[test]
+    fn test_within() {
+        let mut index = SpatialIndex::new();
+        index.insert(&address(12.501, 55.601));
+        index.insert(&address(12.555, 55.655));
+        index.insert(&address(11.0, 55.0));
+        index.insert(&Arc::new(Address::default()));
+
+        let area = Area::BoundingBox("12.5,55.6,12.56,55.66".parse().unwrap());
+        assert_eq!(index.within(&area).count(), 2);
+
+        let area = Area::Circle("12.5,55.6,500".parse().unwrap());
+        assert_eq!(index.within(&area).count(), 1);
+
+        let area = Area::BoundingBox("-1e300,-1e300,1e300,1e300".parse().unwrap());
+        assert_eq!(index.within(&area).count(), 3);
+        let area = Area::BoundingBox("13,56,14,57".parse().unwrap());
+        assert_eq!(index.within(&area).count(), 0);
+        assert_eq!(SpatialIndex::new().within(&area).count(), 0);
+    }
+
+    #[test]
+    fn test_nearest() {
+        let mut index = SpatialIndex::new();
+        for (x, y) in [(12.5, 55.6), (12.52, 55.6), (12.455, 55.6), (11.0, 55.0)] {
+            index.insert(&address(x, y));
+        }
+        index.insert(&Arc::new(Address::default()));
+
+        let nearest: Vec<f64> = index
+            .nearest(Point::new(12.49, 55.6))
+            .map(|address| address.coordinates.unwrap().x)
+            .collect();
+        assert_eq!(nearest, [12.5, 12.52, 12.455, 11.0]);
+        assert_eq!(index.nearest(Point::new(0.0, 0.0)).count(), 4);
+        assert_eq!(SpatialIndex::new().nearest(Point::new(0.0, 0.0)).count(), 0);
+    }
+}
@@ -0,0 +1,152 @@
+use std::collections::BTreeMap;
+
+use crate::address::Address;
+
+/// A street name (vejnavn) across every municipality and postal code it is
+/// used in, with address counts so entries can be dropped again.
+#[derive(Debug)]
+pub struct Street {
+    pub name: String,
+    pub postal_codes: BTreeMap<String, (String, usize)>,
+    pub municipal_codes: BTreeMap<i32, usize>,
+}
+
+/// Streets keyed by lowercase name.
+pub struct StreetIndex {
+    streets: BTreeMap<String, Street>,
+}
+
+impl StreetIndex {
+    pub fn new() -> Self {
+        StreetIndex {
+            streets: BTreeMap::new(),
+        }
+    }
+
+    pub fn insert(&mut self, address: &Address) {
+        let street = self
+            .streets
+            .entry(address.street.to_lowercase())
+            .or_insert_with(|| Street {
+                name: address.street.clone(),
+                postal_codes: BTreeMap::new(),
+                municipal_codes: BTreeMap::new(),
+            });
+        street
+            .postal_codes
+            .entry(address.zip.clone())
+            .or_insert_with(|| (address.city.clone(), 0))
+            .1 += 1;
+        *street
+            .municipal_codes
+            .entry(address.municipal_code)
+            .or_default() += 1;
+    }
+
+    /// Takes back an `insert`, dropping the street with its last address.
+    pub fn remove(&mut self, address: &Address) {
+        let key = address.street.to_lowercase();
+        let Some(street) = self.streets.get_mut(&key) else {
+            return;
+        };
+        if let Some((_, count)) = street.postal_codes.get_mut(&address.zip) {
+            *count -= 1;
+            if *count == 0 {
+                street.postal_codes.remove(&address.zip);
+            }
+        }
+        if let Some(count) = street.municipal_codes.get_mut(&address.municipal_code) {
+            *count -= 1;
+            if *count == 0 {
+                street.municipal_codes.remove(&address.municipal_code);
+            }
+        }
+        if street.municipal_codes.is_empty() {
+            self.streets.remove(&key);
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Street> {
+        self.streets.get(&name.to_lowercase())
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Street> {
+        self.streets.values()
+    }
+
+    /// Streets from `name` onwards in alphabetical order.
+    pub fn range_from(&self, name: &str) -> impl Iterator<Item = &Street> {
+        self.streets
+            .range(name.to_lowercase()..)
+            .map(|(_, street)| street)
+    }
+
+    /// Streets whose name starts with `prefix`.
+    pub fn prefix(&self, prefix: &str) -> impl Iterator<Item = &Street> {
+        let prefix = prefix.to_lowercase();
+        self.streets
+            .range(prefix.clone()..)
+            .take_while(move |(name, _)| name.starts_with(&prefix))
+            .map(|(_, street)| street)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn address(street: &str, zip: &str, city: &str, municipal_code: i32) -> Address {
+        Address {
+            street: street.to_string(),
+            zip: zip.to_string(),
+            city: city.to_string(),
+            municipal_code,
+            ..Address::default()
+        }
+    }
+
+    #[test]
+    fn test_insert() {
+        let mut index = StreetIndex::new();
+        index.insert(&address("Maribovej", "4960", "Holeby", 360));
+        index.insert(&address("Maribovej", "4960", "Holeby", 360));
+        index.insert(&address("Maribovej", "2500", "Valby", 101));
+        index.insert(&address("Maribo Landevej", "4930", "Maribo", 360));
+
+        let street = index.get("MARIBOVEJ").unwrap();
+        assert_eq!(street.municipal_codes.values().sum::<usize>(), 3);
+        assert_eq!(
+            street.postal_codes.keys().collect::<Vec<_>>(),
+            ["2500", "4960"]
+        );
+        assert_eq!(street.municipal_codes[&360], 2);
+    }
+
+    #[test]
+    fn test_remove() {
+        let mut index = StreetIndex::new();
+        index.insert(&address("Maribovej", "4960", "Holeby", 360));
+        index.insert(&address("Maribovej", "2500", "Valby", 101));
+
+        index.remove(&address("Maribovej", "2500", "Valby", 101));
+        let street = index.get("Maribovej").unwrap();
+        assert_eq!(street.postal_codes.keys().collect::<Vec<_>>(), ["4960"]);
+        assert!(!street.municipal_codes.contains_key(&101));
+
+        index.remove(&address("Maribovej", "4960", "Holeby", 360));
+        assert!(index.get("Maribovej").is_none());
+    }
+
+    #[test]
+    fn test_prefix() {
+        let mut index = StreetIndex::new();
+        index.insert(&address("Maribovej", "4960", "Holeby", 360));
+        index.insert(&address("Maribo Landevej", "4930", "Maribo", 360));
+        index.insert(&address("Nakskovvej", "4900", "Nakskov", 360));
+
+        let names: Vec<&str> = index.prefix("mari").map(|s| s.name.as_str()).collect();
+        assert_eq!(names, ["Maribo Landevej", "Maribovej"]);
+        assert_eq!(index.prefix("x").count(), 0);
+        assert_eq!(index.range_from("mb").next().unwrap().name, "Nakskovvej");
+    }
+}
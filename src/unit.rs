@@ -0,0 +1,188 @@
+use std::{fmt, str::FromStr};
+
+use rocket::serde::{Serialize, Serializer};
+
+/// An etage in DAWA's spelling: `k2` and `kl` for basements, `st` for the
+/// ground floor, then `1` to `99`. Orders from the lowest floor up.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Floor(i8);
+
+impl Floor {
+    pub const BASEMENT: Floor = Floor(-1);
+    pub const GROUND: Floor = Floor(0);
+
+    pub fn new(level: i8) -> Floor {
+        Floor(level)
+    }
+}
+
+impl FromStr for Floor {
+    type Err = String;
+
+    /// Parses the ways floors are written, such as `st.`, `stuen`, `kld`,
+    /// `kælder`, `1.`, `01` or `1 sal`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let error = || format!("{} er ikke en gyldig etage", s);
+        let lower = s.trim().to_lowercase();
+        let floor = lower
+            .trim_end_matches("sal")
+            .trim_end()
+            .trim_end_matches('.');
+        match floor {
+            "st" | "stuen" | "stue" => Ok(Floor::GROUND),
+            "kl" | "kld" | "kælder" | "kaelder" | "k1" => Ok(Floor::BASEMENT),
+            _ => {
+                let (sign, digits) = match floor.strip_prefix('k') {
+                    Some(digits) => (-1, digits),
+                    None => (1, floor),
+                };
+                if digits.is_empty()
+                    || digits.len() > 2
+                    || !digits.chars().all(|c| c.is_ascii_digit())
+                {
+                    return Err(error());
+                }
+                match digits.parse::<i8>() {
+                    Ok(level) if level > 0 && (sign > 0 || level <= 9) => Ok(Floor(sign * level)),
+                    _ => Err(error()),
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Display for Floor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            -1 => write!(f, "kl"),
+            0 => write!(f, "st"),
+            level if level < 0 => write!(f, "k{}", -level),
+            level => write!(f, "{}", level),
+        }
+    }
+}
+
+impl Serialize for Floor {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+/// A dør in DAWA's spelling. Orders `tv`, `mf`, `th`, then numbered doors
+/// and anything else.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Door {
+    Left,
+    Middle,
+    Right,
+    Number(u16),
+    Other(String),
+}
+
+impl FromStr for Door {
+    type Err = String;
+
+    /// Parses the ways doors are written, such as `t.v.`, `th.`, `venstre`,
+    /// `midt` or `3`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let door = s.trim().replace('.', "").to_lowercase();
+        match door.as_str() {
+            "tv" | "venstre" => Ok(Door::Left),
+            "mf" | "midt" | "midtfor" => Ok(Door::Middle),
+            "th" | "højre" | "hoejre" => Ok(Door::Right),
+            _ if !door.is_empty() && door.chars().all(|c| c.is_ascii_digit()) => door
+                .parse()
+                .map(Door::Number)
+                .map_err(|_| format!("{} er ikke en gyldig dør", s)),
+            _ if !door.is_empty()
+                && door.chars().count() <= 4
+                && door.chars().all(|c| c.is_alphanumeric()) =>
+            {
+                Ok(Door::Other(door))
+            }
+            _ => Err(format!("{} er ikke en gyldig dør", s)),
+        }
+    }
+}
+
+impl fmt::Display for Door {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Door::Left => write!(f, "tv"),
+            Door::Middle => write!(f, "mf"),
+            Door::Right => write!(f, "th"),
+            Door::Number(number) => write!(f, "{}", number),
+            Door::Other(door) => write!(f, "{}", door),
+        }
+    }
+}
+
+impl Serialize for Door {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn floor(s: &str) -> Option<Floor> {
+        s.parse().ok()
+    }
+
+    fn door(s: &str) -> Option<Door> {
+        s.parse().ok()
+    }
+
+    #[test]
+    fn test_parse_floor() {
+        for spelling in ["st", "st.", "Stuen", "stue"] {
+            assert_eq!(floor(spelling), Some(Floor::GROUND), "{}", spelling);
+        }
+        for spelling in ["kl", "kld.", "kælder", "Kaelder"] {
+            assert_eq!(floor(spelling), Some(Floor::BASEMENT), "{}", spelling);
+        }
+        assert_eq!(floor("1."), Some(Floor::new(1)));
+        assert_eq!(floor("01"), Some(Floor::new(1)));
+        assert_eq!(floor("1 sal"), Some(Floor::new(1)));
+        assert_eq!(floor("1.sal"), Some(Floor::new(1)));
+        assert_eq!(floor("k2"), Some(Floor::new(-2)));
+        assert_eq!(floor("0"), None);
+        assert_eq!(floor("100"), None);
+        assert_eq!(floor("tv"), None);
+        assert_eq!(Floor::new(-2).to_string(), "k2");
+        assert_eq!(Floor::BASEMENT.to_string(), "kl");
+        assert_eq!(Floor::new(12).to_string(), "12");
+    }
+
+    #[test]
+    fn test_parse_door() {
+        assert_eq!(door("t.v."), Some(Door::Left));
+        assert_eq!(door("TH"), Some(Door::Right));
+        assert_eq!(door("midt"), Some(Door::Middle));
+        assert_eq!(door("12"), Some(Door::Number(12)));
+        assert_eq!(door("a1"), Some(Door::Other("a1".to_string())));
+        assert_eq!(door(""), None);
+        assert_eq!(door("lejlighed"), None);
+    }
+
+    #[test]
+    fn test_order() {
+        let mut floors: Vec<Floor> = ["2", "st", "k2", "10", "kl", "1"]
+            .iter()
+            .map(|s| s.parse().unwrap())
+            .collect();
+        floors.sort();
+        let floors: Vec<String> = floors.iter().map(|floor| floor.to_string()).collect();
+        assert_eq!(floors, ["k2", "kl", "st", "1", "2", "10"]);
+
+        let mut doors: Vec<Door> = ["th", "2", "tv", "10", "mf"]
+            .iter()
+            .map(|s| s.parse().unwrap())
+            .collect();
+        doors.sort();
+        let doors: Vec<String> = doors.iter().map(|door| door.to_string()).collect();
+        assert_eq!(doors, ["tv", "mf", "th", "2", "10"]);
+    }
+}
@@ -0,0 +1,256 @@
+use std::{fmt, sync::Arc};
+
+use crate::address::Address;
+use crate::address_parser::ParsedAddress;
+
+/// How sure a wash is of its best match, as in DAWA's datavask.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Category {
+    /// The address matched exactly
+    A,
+    /// The address matched with minor differences
+    B,
+    /// The match is uncertain
+    C,
+}
+
+impl fmt::Display for Category {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let category = match self {
+            Category::A => "A",
+            Category::B => "B",
+            Category::C => "C",
+        };
+        write!(f, "{}", category)
+    }
+}
+
+/// Edit distances between the parsed text and a candidate, per field. Fields
+/// left out of the text count as no difference, except floor and door.
+#[derive(Debug, Default, PartialEq, Eq, Clone)]
+pub struct Differences {
+    pub street: usize,
+    pub number: usize,
+    pub floor: usize,
+    pub door: usize,
+    pub placename: usize,
+    pub zip: usize,
+    pub city: usize,
+}
+
+impl Differences {
+    pub fn total(&self) -> usize {
+        self.street + self.number + self.floor + self.door + self.placename + self.zip + self.city
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Candidate {
+    pub address: Arc<Address>,
+    /// 1.0 for a perfect match down to 0.0
+    pub score: f64,
+    pub differences: Differences,
+}
+
+#[derive(Debug)]
+pub struct Washed {
+    pub category: Category,
+    pub parsed: ParsedAddress,
+    /// Best match first
+    pub candidates: Vec<Candidate>,
+}
+
+/// Levenshtein distance, ignoring case.
+pub fn distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.to_lowercase().chars().collect();
+    let b: Vec<char> = b.to_lowercase().chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    let mut current = vec![0; b.len() + 1];
+    for (i, a) in a.iter().enumerate() {
+        current[0] = i + 1;
+        for (j, b) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a != b);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+    previous[b.len()]
+}
+
+/// 1.0 for equal strings down to 0.0 for nothing in common.
+pub fn similarity(a: &str, b: &str) -> f64 {
+    let length = a.chars().count().max(b.chars().count());
+    if length == 0 {
+        return 1.0;
+    }
+    1.0 - distance(a, b) as f64 / length as f64
+}
+
+const STREET_WEIGHT: f64 = 4.0;
+const NUMBER_WEIGHT: f64 = 3.0;
+const ZIP_WEIGHT: f64 = 2.0;
+const CITY_WEIGHT: f64 = 1.0;
+const UNIT_WEIGHT: f64 = 1.0;
+const PLACENAME_WEIGHT: f64 = 0.5;
+
+/// Scores `address` against the parsed text. Street and house number weigh
+/// the most, the fields the text leaves out are not scored, except floor and
+/// door, which must be absent from the address too.
+pub fn compare(parsed: &ParsedAddress, address: &Arc<Address>) -> Candidate {
+    let mut differences = Differences::default();
+    let mut score = 0.0;
+    let mut weight = 0.0;
+
+    let street = parsed.street.as_deref().unwrap_or("");
+    differences.street = distance(street, &address.street);
+    score += STREET_WEIGHT * similarity(street, &address.street);
+    weight += STREET_WEIGHT;
+
+    let number = parsed.number.as_deref().unwrap_or("");
+    differences.number = distance(number, &address.number);
+    score += NUMBER_WEIGHT
+        * if differences.number == 0 {
+            1.0
+        } else if number.trim_end_matches(char::is_alphabetic)
+            == address.number.trim_end_matches(char::is_alphabetic)
+        {
+            0.5
+        } else {
+            0.0
+        };
+    weight += NUMBER_WEIGHT;
+
+    let floor = parsed.floor.map(|floor| floor.to_string());
+    let door = parsed.door.as_ref().map(|door| door.to_string());
+    for (unit, value, difference) in [
+        (floor, &address.floor, &mut differences.floor),
+        (door, &address.door, &mut differences.door),
+    ] {
+        *difference = distance(unit.as_deref().unwrap_or(""), value);
+        score += UNIT_WEIGHT * f64::from(u8::from(*difference == 0));
+        weight += UNIT_WEIGHT;
+    }
+
+    if let Some(zip) = &parsed.zip {
+        // Mail to a stormodtager is addressed with its own postal code
+        differences.zip = [&address.zip, &address.large_recipient_zip]
+            .into_iter()
+            .filter(|nr| !nr.is_empty())
+            .map(|nr| distance(zip, nr))
+            .min()
+            .unwrap_or(zip.len());
+        score += ZIP_WEIGHT * (1.0 - differences.zip as f64 / zip.len().max(4) as f64).max(0.0);
+        weight += ZIP_WEIGHT;
+    }
+
+    if let Some(city) = &parsed.city {
+        let (difference, city_similarity) = [
+            &address.city,
+            &address.placename,
+            &address.large_recipient_city,
+        ]
+        .into_iter()
+        .filter(|name| !name.is_empty())
+        .map(|name| (distance(city, name), similarity(city, name)))
+        .min_by_key(|(difference, _)| *difference)
+        .unwrap_or((city.chars().count(), 0.0));
+        differences.city = difference;
+        score += CITY_WEIGHT * city_similarity;
+        weight += CITY_WEIGHT;
+    }
+
+    if let Some(placename) = &parsed.placename {
+        differences.placename = distance(placename, &address.placename);
+        score += PLACENAME_WEIGHT * similarity(placename, &address.placename);
+        weight += PLACENAME_WEIGHT;
+    }
+
+    Candidate {
+        address: address.clone(),
+        score: score / weight,
+        differences,
+    }
+}
+
+/// Lowest score for a category B match
+const CATEGORY_B_SCORE: f64 = 0.8;
+
+/// Sorts the candidates best first and tells how certain the best one is: A
+/// when it matches without differences, B when it is close and beats every
+/// other candidate, otherwise C.
+pub fn categorize(candidates: &mut [Candidate]) -> Category {
+    candidates.sort_by(|a, b| {
+        b.score
+            .total_cmp(&a.score)
+            .then_with(|| a.differences.total().cmp(&b.differences.total()))
+            .then_with(|| a.address.cmp(&b.address))
+    });
+    let Some(best) = candidates.first() else {
+        return Category::C;
+    };
+    let unique = candidates
+        .get(1)
+        .is_none_or(|runner_up| runner_up.score < best.score);
+    match (best.differences.total(), unique) {
+        (0, true) => Category::A,
+        (_, true) if best.score >= CATEGORY_B_SCORE => Category::B,
+        _ => Category::C,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::address_parser::parse;
+
+    fn address(street: &str, number: &str, floor: &str, door: &str) -> Arc<Address> {
+        Arc::new(Address {
+            street: street.to_string(),
+            number: number.to_string(),
+            floor: floor.to_string(),
+            door: door.to_string(),
+            zip: "2500".to_string(),
+            city: "Valby".to_string(),
+            ..Address::default()
+        })
+    }
+
+    #[test]
+    fn test_distance() {
+        assert_eq!(distance("Maribovej", "maribovej"), 0);
+        assert_eq!(distance("Maribovej", "Mariboevej"), 1);
+        assert_eq!(distance("Sofiesvej", "Sofies Vej"), 1);
+        assert_eq!(distance("", "st"), 2);
+        assert_eq!(similarity("", ""), 1.0);
+    }
+
+    #[test]
+    fn test_categorize() {
+        let addresses = [
+            address("Maribovej", "15", "st", "tv"),
+            address("Maribovej", "15", "st", "th"),
+            address("Maribovej", "15", "1", "tv"),
+        ];
+        let wash = |text: &str| {
+            let parsed = parse(text);
+            let mut candidates: Vec<Candidate> = addresses
+                .iter()
+                .map(|address| compare(&parsed, address))
+                .collect();
+            let category = categorize(&mut candidates);
+            (category, candidates)
+        };
+
+        let (category, candidates) = wash("Maribovej 15, st. th, 2500 Valby");
+        assert_eq!(category, Category::A);
+        assert_eq!(candidates[0].address.door, "th");
+
+        let (category, candidates) = wash("Mariboevej 15 1 tv 2500 Valby");
+        assert_eq!(category, Category::B);
+        assert_eq!(candidates[0].differences.street, 1);
+        assert_eq!(candidates[0].differences.total(), 1);
+
+        let (category, _) = wash("Maribovej 15, 2500 Valby");
+        assert_eq!(category, Category::C);
+    }
+}